@@ -0,0 +1,125 @@
+//! Renders chords, or the chords which solve a level, to a WAV file without opening a window or an audio device.
+//!
+//! Usage: `cargo run --release --bin render_wav -- OUT.wav CHORDS... [OPTIONS]`
+//!
+//! Each chord is a chord symbol, e.g. `Am7` or `C/E`, or pitches joined with `+`, e.g. `C4+E4+G4`.
+//! `--level N` renders the cluster which completes each objective of level N in its shortest solution instead,
+//! from the campaign in `assets/levels` or the file given by `--levels FILE.levels.ron`.
+//!
+//! `--instrument NAME` and `--tuning SYSTEM` are written as in level files and default to the level's, if there is one,
+//! `--reference HZ` sets the frequency of A4 and `--seconds SECONDS` how long each chord sounds.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use chord_fusion::campaign::{Campaign, CAMPAIGN_PATH};
+use chord_fusion::chord::ChordSymbol;
+use chord_fusion::cluster::{Cluster, Pitch};
+use chord_fusion::instrument::Instrument;
+use chord_fusion::render::*;
+use chord_fusion::tuning::{Tuning, TuningSystem};
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut output = None::<PathBuf>;
+    let mut chords = Vec::<String>::new();
+    let mut level_number = None::<usize>;
+    let mut levels_path = PathBuf::from("assets").join(CAMPAIGN_PATH);
+    let mut instrument = None::<Instrument>;
+    let mut tuning_system = None::<TuningSystem>;
+    let mut tuning = Tuning::default();
+    let mut settings = RenderSettings::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{arg} must be followed by a value"))
+        };
+        match arg.as_str() {
+            "--level" => level_number = Some(parse_number(&arg, &value()?)?),
+            "--levels" => levels_path = value()?.into(),
+            "--instrument" => instrument = Some(parse_ron(&arg, &value()?)?),
+            "--tuning" => tuning_system = Some(parse_ron(&arg, &value()?)?),
+            "--reference" => tuning.reference = parse_number(&arg, &value()?)?,
+            "--seconds" => settings.chord_seconds = parse_number(&arg, &value()?)?,
+            _ if output.is_none() => output = Some(arg.into()),
+            _ => chords.push(arg),
+        }
+    }
+
+    let output = output.ok_or("Usage: render_wav OUT.wav CHORDS... [--level N] [OPTIONS]")?;
+
+    let clusters = match level_number {
+        Some(number) => {
+            let bytes = std::fs::read(&levels_path)
+                .map_err(|e| format!("{}: {e}", levels_path.display()))?;
+            let campaign = Campaign::from_bytes(&bytes)
+                .map_err(|e| format!("{}: {e}", levels_path.display()))?;
+            let level = campaign.get_level(number);
+
+            instrument = instrument.or(level.instrument);
+            tuning_system = tuning_system.or_else(|| level.tuning.clone());
+            level_chords(&level).map_err(|e| format!("level {number}: {e}"))?
+        }
+        None => chords
+            .iter()
+            .map(|c| parse_chord(c))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    if clusters.is_empty() {
+        return Err("There are no chords to render".to_string());
+    }
+
+    if let Some(system) = tuning_system {
+        tuning.system = system;
+    }
+    settings.instrument = instrument.unwrap_or_default();
+    settings.frequencies = tuning_frequencies(&tuning, &PathBuf::from("assets"))?;
+
+    render_to_file(&clusters, &settings, &output)
+        .map_err(|e| format!("{}: {e}", output.display()))?;
+
+    println!(
+        "Rendered {} chord(s) to {}",
+        clusters.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// A chord symbol, or pitches joined with `+`
+fn parse_chord(text: &str) -> Result<Cluster, String> {
+    if text.contains('+') {
+        let pitches = text
+            .split('+')
+            .map(|p| p.parse::<Pitch>())
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("'{text}': {e}"))?;
+        Ok(Cluster { pitches })
+    } else {
+        Ok(Cluster {
+            pitches: text.parse::<ChordSymbol>()?.pitches().into_iter().collect(),
+        })
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{arg} must be followed by a number, not '{value}'"))
+}
+
+fn parse_ron<T: serde::de::DeserializeOwned>(arg: &str, value: &str) -> Result<T, String> {
+    ron::from_str(value).map_err(|e| format!("{arg} '{value}': {e}"))
+}
@@ -0,0 +1,104 @@
+//! Checks that levels are well formed and solvable.
+//!
+//! Usage: `cargo run --release --bin validate_levels -- [FILE.levels.ron]... [--random COUNT]`
+//!
+//! With no files, the campaign in `assets/levels` is checked.
+//! `--random` also checks that many randomly generated levels after the end of each campaign.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use chord_fusion::campaign::{Campaign, CAMPAIGN_PATH};
+use chord_fusion::chord::Chord;
+use chord_fusion::level::GameLevel;
+use chord_fusion::solver::Solution;
+
+const DEFAULT_RANDOM_LEVELS: usize = 10;
+
+fn main() -> ExitCode {
+    let mut paths = Vec::<PathBuf>::new();
+    let mut random_levels = DEFAULT_RANDOM_LEVELS;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--random" {
+            match args.next().and_then(|x| x.parse().ok()) {
+                Some(count) => random_levels = count,
+                None => {
+                    eprintln!("--random must be followed by a number of levels");
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            paths.push(arg.into());
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from("assets").join(CAMPAIGN_PATH));
+    }
+
+    let mut failures = 0;
+
+    for path in paths {
+        println!("{}", path.display());
+
+        let campaign = match std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| Campaign::from_bytes(&bytes).map_err(|e| e.to_string()))
+        {
+            Ok(campaign) => campaign,
+            Err(err) => {
+                println!("  FAIL could not load: {err}");
+                failures += 1;
+                continue;
+            }
+        };
+
+        for i in 1..=(campaign.levels.len() + random_levels) {
+            let level = campaign.get_level(i);
+            let title = format!("{i:>3} {:<6} {}", level.header, level.name);
+
+            match validate(&level) {
+                Ok(solution) => println!("  ok   {title} ({} moves)", solution.moves.len()),
+                Err(err) => {
+                    println!("  FAIL {title}: {err}");
+                    failures += 1;
+                }
+            }
+        }
+    }
+
+    if failures > 0 {
+        println!("{failures} level(s) failed");
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn validate(level: &GameLevel) -> Result<Solution, String> {
+    if level.objectives.is_empty() {
+        return Err("level has no objectives".to_string());
+    }
+
+    let smallest_chord = Chord::all().keys().map(|k| k.len()).min().unwrap_or(1);
+    let notes_needed: usize = level
+        .objectives
+        .iter()
+        .map(|o| {
+            o.chord
+                .map(|c| c.intervals().len())
+                .unwrap_or(smallest_chord)
+        })
+        .sum();
+
+    let notes = level.pitches().len();
+    if notes_needed > notes {
+        return Err(format!(
+            "objectives need at least {notes_needed} notes but there are only {notes}"
+        ));
+    }
+
+    level.solve().map_err(|e| e.to_string())
+}
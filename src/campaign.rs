@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::generator::*;
+use crate::level::{CurrentLevel, GameLevel, GameMode};
+
+pub const CAMPAIGN_PATH: &str = "levels/campaign.levels.ron";
+
+pub struct CampaignPlugin;
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Campaign>()
+            .init_asset_loader::<CampaignLoader>()
+            .add_startup_system(load_campaign)
+            .add_system(generate_next_level);
+    }
+}
+
+/// An ordered list of levels, loaded from a `.levels.ron` file
+#[derive(Debug, Clone, Default, Deserialize, TypeUuid)]
+#[serde(transparent)]
+#[uuid = "8845e57d-bed2-4f8c-b9fd-bf83724b4e99"]
+pub struct Campaign {
+    pub levels: Vec<GameLevel>,
+    /// Set if the file could not be parsed, so the error can be shown in game. The levels are then the ones from the last time it could be
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl Campaign {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ron::Error> {
+        ron::de::from_bytes(bytes)
+    }
+
+    /// Get a level by its one-based index. Levels past the end of the campaign are generated, and get harder as they go.
+    pub fn get_level(&self, i: usize) -> GameLevel {
+        match i.checked_sub(1).and_then(|index| self.levels.get(index)) {
+            Some(level) => level.clone(),
+            None => Self::generate_level(i, self.levels.len()),
+        }
+    }
+
+    fn generate_level(i: usize, campaign_length: usize) -> GameLevel {
+        let difficulty = i.saturating_sub(campaign_length + 1);
+        let mut level = LevelParameters::for_difficulty(difficulty).generate(i as u64);
+        level.header = level_header(i, level.objectives.iter().find_map(|o| o.chord));
+        level
+    }
+}
+
+/// Remembers the levels in each file the last time it was parsed, so a mistake while editing one doesn't lose them
+#[derive(Default)]
+pub struct CampaignLoader {
+    last_good: Mutex<HashMap<PathBuf, Vec<GameLevel>>>,
+}
+
+impl AssetLoader for CampaignLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().to_path_buf();
+            let mut last_good = self.last_good.lock().unwrap();
+            let campaign = match Campaign::from_bytes(bytes) {
+                Ok(campaign) => {
+                    last_good.insert(path, campaign.levels.clone());
+                    campaign
+                }
+                Err(err) => Campaign {
+                    levels: last_good.get(&path).cloned().unwrap_or_default(),
+                    error: Some(format!("{}: {err}", path.display())),
+                },
+            };
+            load_context.set_default_asset(LoadedAsset::new(campaign));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["levels.ron"]
+    }
+}
+
+pub struct CampaignHandle(pub Handle<Campaign>);
+
+fn load_campaign(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CampaignHandle(asset_server.load(CAMPAIGN_PATH)));
+}
+
+/// Generate the level after this one in the background, so it is ready by the time this one is completed.
+/// The generator keeps the level until it is needed. The task is kept so it is cancelled if it hasn't started by the time another level is wanted
+fn generate_next_level(
+    current_level: Res<CurrentLevel>,
+    game_mode: Res<GameMode>,
+    campaign_handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+    mut generating: Local<Option<((usize, usize), Task<()>)>>,
+) {
+    if !current_level.is_changed() || *game_mode != GameMode::Campaign {
+        return;
+    }
+
+    if let Some(campaign) = campaigns.get(&campaign_handle.0) {
+        let next = current_level.0 + 1;
+        let campaign_length = campaign.levels.len();
+        let already_generating = generating
+            .as_ref()
+            .map(|(key, _)| *key == (next, campaign_length))
+            .unwrap_or(false);
+
+        if next > campaign_length && !already_generating {
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                Campaign::generate_level(next, campaign_length);
+            });
+            *generating = Some(((next, campaign_length), task));
+        }
+    }
+}
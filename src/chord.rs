@@ -1,13 +1,18 @@
-use std::{collections::BTreeMap, fmt::Debug, hash::Hash};
+use std::{collections::BTreeMap, fmt::Debug, hash::Hash, str::FromStr};
 
 use bevy::render::once_cell::sync::OnceCell;
 
 use itertools::Itertools;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use strum::{EnumCount, EnumIter, FromRepr, IntoEnumIterator};
 
-use crate::cluster::Note;
+use crate::cluster::{Note, Pitch};
+use crate::spelling::SpelledNote;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, EnumIter, EnumCount, FromRepr)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Debug, EnumIter, EnumCount, FromRepr, Deserialize, Serialize,
+)]
 pub enum Chord {
     Major,
     Minor,
@@ -15,6 +20,7 @@ pub enum Chord {
     Augmented,
     Suspended2,
     Suspended4,
+    Power,
 
     Dominant7,
     Major7,
@@ -24,7 +30,20 @@ pub enum Chord {
     Diminished7,
     Augmented7,
     AugmentedMaj7,
+    Dominant7Sus4,
+    Major6,
+    Minor6,
+    Add9,
+    MinorAdd9,
+
+    Dominant9,
+    Major9,
+    Minor9,
+    Dominant7Flat9,
+    Dominant7Sharp9,
+
     Dominant11,
+    Dominant13,
 }
 
 impl std::fmt::Display for Chord {
@@ -43,6 +62,41 @@ impl Chord {
 }
 
 impl Chord {
+    /// The suffix used in lead sheet chord symbols, e.g. the "m7" in "Am7"
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Major => "",
+            Self::Minor => "m",
+            Self::Diminished => "dim",
+            Self::Augmented => "aug",
+            Self::Suspended2 => "sus2",
+            Self::Suspended4 => "sus4",
+            Self::Power => "5",
+
+            Self::Dominant7 => "7",
+            Self::Major7 => "maj7",
+            Self::Minor7 => "m7",
+            Self::MinorMajor7 => "m(maj7)",
+            Self::HalfDiminished => "m7b5",
+            Self::Diminished7 => "dim7",
+            Self::Augmented7 => "aug7",
+            Self::AugmentedMaj7 => "augmaj7",
+            Self::Dominant7Sus4 => "7sus4",
+            Self::Major6 => "6",
+            Self::Minor6 => "m6",
+            Self::Add9 => "add9",
+            Self::MinorAdd9 => "madd9",
+
+            Self::Dominant9 => "9",
+            Self::Major9 => "maj9",
+            Self::Minor9 => "m9",
+            Self::Dominant7Flat9 => "7b9",
+            Self::Dominant7Sharp9 => "7#9",
+
+            Self::Dominant11 => "11",
+            Self::Dominant13 => "13",
+        }
+    }
     pub fn short_name(&self) -> &'static str {
         match self {
             Self::Major => "M",
@@ -51,6 +105,7 @@ impl Chord {
             Self::Augmented => "+",
             Self::Suspended2 => "s2",
             Self::Suspended4 => "s4",
+            Self::Power => "5",
 
             Self::Dominant7 => "7",
             Self::Major7 => "M7",
@@ -60,7 +115,20 @@ impl Chord {
             Self::Diminished7 => "o7",
             Self::Augmented7 => "+7",
             Self::AugmentedMaj7 => "+M7",
+            Self::Dominant7Sus4 => "7s4",
+            Self::Major6 => "6",
+            Self::Minor6 => "m6",
+            Self::Add9 => "a9",
+            Self::MinorAdd9 => "ma9",
+
+            Self::Dominant9 => "9",
+            Self::Major9 => "M9",
+            Self::Minor9 => "m9",
+            Self::Dominant7Flat9 => "7b9",
+            Self::Dominant7Sharp9 => "7#9",
+
             Self::Dominant11 => "11",
+            Self::Dominant13 => "13",
         }
     }
     pub fn nice_name(&self) -> &'static str {
@@ -71,6 +139,7 @@ impl Chord {
             Self::Augmented => "aug",
             Self::Suspended2 => "sus2",
             Self::Suspended4 => "sus4",
+            Self::Power => "power",
 
             Self::Dominant7 => "7",
             Self::Major7 => "major7",
@@ -80,9 +149,23 @@ impl Chord {
             Self::Diminished7 => "dim7",
             Self::Augmented7 => "aug7",
             Self::AugmentedMaj7 => "aug major7",
+            Self::Dominant7Sus4 => "7sus4",
+            Self::Major6 => "6",
+            Self::Minor6 => "minor6",
+            Self::Add9 => "add9",
+            Self::MinorAdd9 => "minor add9",
+
+            Self::Dominant9 => "9",
+            Self::Major9 => "major9",
+            Self::Minor9 => "minor9",
+            Self::Dominant7Flat9 => "7 flat9",
+            Self::Dominant7Sharp9 => "7 sharp9",
+
             Self::Dominant11 => "11",
+            Self::Dominant13 => "13",
         }
     }
+    /// The notes of the chord above the root, stacked in thirds: third, fifth, seventh, then any extensions
     pub fn intervals(&self) -> Vec<u8> {
         match self {
             Self::Major => vec![0, 4, 7],
@@ -91,6 +174,7 @@ impl Chord {
             Self::Augmented => vec![0, 4, 8],
             Self::Suspended2 => vec![0, 2, 7],
             Self::Suspended4 => vec![0, 5, 7],
+            Self::Power => vec![0, 7],
 
             Self::Dominant7 => vec![0, 4, 7, 10],
             Self::Major7 => vec![0, 4, 7, 11],
@@ -100,12 +184,639 @@ impl Chord {
             Self::Diminished7 => vec![0, 3, 6, 9],
             Self::Augmented7 => vec![0, 4, 8, 10],
             Self::AugmentedMaj7 => vec![0, 4, 8, 11],
-            Self::Dominant11 => vec![0, 5, 7, 10],
+            Self::Dominant7Sus4 => vec![0, 5, 7, 10],
+            Self::Major6 => vec![0, 4, 7, 9],
+            Self::Minor6 => vec![0, 3, 7, 9],
+            Self::Add9 => vec![0, 4, 7, 2],
+            Self::MinorAdd9 => vec![0, 3, 7, 2],
+
+            Self::Dominant9 => vec![0, 4, 7, 10, 2],
+            Self::Major9 => vec![0, 4, 7, 11, 2],
+            Self::Minor9 => vec![0, 3, 7, 10, 2],
+            Self::Dominant7Flat9 => vec![0, 4, 7, 10, 1],
+            Self::Dominant7Sharp9 => vec![0, 4, 7, 10, 3],
+
+            Self::Dominant11 => vec![0, 4, 7, 10, 2, 5],
+            Self::Dominant13 => vec![0, 4, 7, 10, 2, 9],
+        }
+    }
+
+    /// How hard the chord is to read, for choosing between readings of the same notes.
+    /// Chords with fewer notes are simpler, and then the more common ones
+    pub fn complexity(&self) -> (usize, u8) {
+        let rarity = match self {
+            Self::Major | Self::Power => 0,
+            Self::Minor => 1,
+            Self::Suspended4 => 2,
+            Self::Suspended2 => 3,
+            Self::Diminished => 4,
+            Self::Augmented => 5,
+
+            Self::Dominant7 => 0,
+            Self::Minor7 => 1,
+            Self::Major7 => 2,
+            Self::Major6 => 3,
+            Self::Minor6 => 4,
+            Self::HalfDiminished => 5,
+            Self::Add9 => 6,
+            Self::Dominant7Sus4 => 7,
+            Self::MinorAdd9 => 8,
+            Self::Diminished7 => 9,
+            Self::MinorMajor7 => 10,
+            Self::Augmented7 => 11,
+            Self::AugmentedMaj7 => 12,
+
+            Self::Dominant9 => 0,
+            Self::Minor9 => 1,
+            Self::Major9 => 2,
+            Self::Dominant7Flat9 => 3,
+            Self::Dominant7Sharp9 => 4,
+
+            Self::Dominant11 => 0,
+            Self::Dominant13 => 1,
+        };
+
+        (self.intervals().len(), rarity)
+    }
+
+    /// How many letters above the root this interval is written, so thirds are always written as thirds
+    pub fn letter_offset(&self, interval: u8) -> u8 {
+        match (self, interval) {
+            (Self::Dominant7Sharp9, 3) => 1,
+            (Self::Diminished7, 9) => 6,
+            (_, 0) => 0,
+            (_, 1 | 2) => 1,
+            (_, 3 | 4) => 2,
+            (_, 5) => 3,
+            (_, 6..=8) => 4,
+            (_, 9) => 5,
+            _ => 6,
+        }
+    }
+
+    /// The inversion with the note this far above the root in the bass, by which note of the chord it is rather than where it comes in `intervals`
+    pub fn inversion(&self, interval: u8) -> Option<Inversion> {
+        let intervals = self.intervals();
+        if !intervals.contains(&interval) {
+            return None;
         }
+        let has_letter = |letter| intervals.iter().any(|&i| self.letter_offset(i) == letter);
+
+        Some(match self.letter_offset(interval) {
+            0 => Inversion::RootPosition,
+            2 => Inversion::First,
+            4 => Inversion::Second,
+            6 => Inversion::Third,
+            1 | 3 if !has_letter(2) => Inversion::First,
+            1 => Inversion::Fourth,
+            3 => Inversion::Fifth,
+            _ if !has_letter(6) => Inversion::Third,
+            _ => Inversion::Sixth,
+        })
     }
+
+    /// Find a chord by the suffix of a chord symbol, e.g. "m7" or "maj7"
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        Chord::iter().find(|c| c.symbol() == symbol || c.aliases().contains(&symbol))
+    }
+
+    /// Other ways of writing the chord symbol which are accepted when parsing
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Self::Major => &["maj", "M", "Δ"],
+            Self::Minor => &["min", "-"],
+            Self::Diminished => &["o", "°"],
+            Self::Augmented => &["+"],
+            Self::Suspended4 => &["sus"],
+            Self::Power => &["(no3)"],
+            Self::Dominant7 => &["dom7"],
+            Self::Major7 => &["ma7", "M7", "Δ7"],
+            Self::Minor7 => &["min7", "-7"],
+            Self::MinorMajor7 => &["mM7", "mMaj7", "m(M7)", "minmaj7"],
+            Self::HalfDiminished => &["ø", "ø7", "m7(b5)", "-7b5"],
+            Self::Diminished7 => &["o7", "°7"],
+            Self::Augmented7 => &["+7", "7#5", "7+5"],
+            Self::AugmentedMaj7 => &["maj7#5", "+maj7", "+M7"],
+            Self::Dominant7Sus4 => &["7sus"],
+            Self::Major6 => &["maj6"],
+            Self::Minor6 => &["min6", "-6"],
+            Self::MinorAdd9 => &["m(add9)"],
+            Self::Dominant9 => &["dom9"],
+            Self::Major9 => &["ma9", "M9", "Δ9"],
+            Self::Minor9 => &["min9", "-9"],
+            Self::Dominant7Flat9 => &["7(b9)"],
+            Self::Dominant7Sharp9 => &["7(#9)"],
+            _ => &[],
+        }
+    }
+
+    /// Every chord, by its sorted intervals
     pub fn all() -> &'static BTreeMap<Vec<u8>, Self> {
-        CHORDS.get_or_init(|| BTreeMap::from_iter(Chord::iter().map(|c| (c.intervals(), c))))
+        CHORDS.get_or_init(|| {
+            BTreeMap::from_iter(
+                Chord::iter().map(|c| (c.intervals().into_iter().sorted().collect(), c)),
+            )
+        })
     }
 }
 
 static CHORDS: OnceCell<BTreeMap<Vec<u8>, Chord>> = OnceCell::new();
+
+/// Which note of a chord is in the bass
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, EnumIter, Deserialize, Serialize,
+)]
+pub enum Inversion {
+    RootPosition,
+    /// The third, or the suspended note in its place, in the bass
+    First,
+    Second,
+    /// The seventh, or an added sixth, in the bass
+    Third,
+    /// The ninth in the bass
+    Fourth,
+    /// The eleventh in the bass
+    Fifth,
+    /// The thirteenth in the bass
+    Sixth,
+}
+
+impl Inversion {
+    pub fn nice_name(&self) -> &'static str {
+        match self {
+            Self::RootPosition => "root position",
+            Self::First => "1st inversion",
+            Self::Second => "2nd inversion",
+            Self::Third => "3rd inversion",
+            Self::Fourth => "4th inversion",
+            Self::Fifth => "5th inversion",
+            Self::Sixth => "6th inversion",
+        }
+    }
+}
+
+impl std::fmt::Display for Inversion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.nice_name())
+    }
+}
+
+/// A chord with a particular root, and which of its notes is in the bass
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ChordAnalysis {
+    pub root: Note,
+    pub chord: Chord,
+    pub bass: Note,
+    pub inversion: Inversion,
+}
+
+impl ChordAnalysis {
+    /// Returns None if the bass is not one of the chord's notes
+    pub fn new(root: Note, chord: Chord, bass: Note) -> Option<Self> {
+        let interval = (bass.0 + 12 - root.0) % 12;
+
+        Some(Self {
+            root,
+            chord,
+            bass,
+            inversion: chord.inversion(interval)?,
+        })
+    }
+}
+
+impl std::fmt::Display for ChordAnalysis {
+    /// Slash chord notation, e.g. "Am7" or "C/E"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", ChordSymbol::from(*self))
+    }
+}
+
+/// A chord with a root, as written on a lead sheet, e.g. "F#m7b5" or "E7/G#"
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ChordSymbol {
+    pub root: Note,
+    pub chord: Chord,
+    /// The note after the slash, if there is one
+    pub bass: Option<Note>,
+    /// How the root was written, if the symbol was parsed, so it is written back the same way
+    pub spelled_root: Option<SpelledNote>,
+    /// How the bass was written, if the symbol was parsed
+    pub spelled_bass: Option<SpelledNote>,
+}
+
+impl ChordSymbol {
+    /// The chord as close stacked pitches from the root in the octave from middle C, with any bass note in the octave below.
+    /// Used for the orbs of a level's chords and when rendering chords
+    pub fn pitches(&self) -> Vec<Pitch> {
+        let bass = self.bass.filter(|b| *b != self.root);
+        let mut pitches = bass
+            .map(|b| Pitch::new(b, Pitch::DEFAULT_OCTAVE - 1))
+            .into_iter()
+            .collect_vec();
+
+        let mut pitch = Pitch::new(self.root, Pitch::DEFAULT_OCTAVE);
+        let mut previous = 0;
+        for interval in self.chord.intervals() {
+            //Each note of the stack goes above the one before
+            let step = (interval + 12 - previous) % 12;
+            pitch = Pitch(pitch.0 + step);
+            previous = interval;
+
+            if Some(pitch.note()) != bass {
+                pitches.push(pitch);
+            }
+        }
+        pitches
+    }
+
+    /// The notes of the chord, plus the bass if it is not one of them
+    pub fn get_notes(&self) -> Vec<Note> {
+        let mut notes = self.chord.get_notes(self.root);
+        if let Some(bass) = self.bass {
+            if !notes.contains(&bass) {
+                notes.push(bass);
+            }
+        }
+        notes
+    }
+}
+
+impl From<ChordAnalysis> for ChordSymbol {
+    fn from(analysis: ChordAnalysis) -> Self {
+        Self {
+            root: analysis.root,
+            chord: analysis.chord,
+            bass: if analysis.inversion == Inversion::RootPosition {
+                None
+            } else {
+                Some(analysis.bass)
+            },
+            spelled_root: None,
+            spelled_bass: None,
+        }
+    }
+}
+
+impl std::fmt::Display for ChordSymbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.spelled_name(None))
+    }
+}
+
+impl FromStr for ChordSymbol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, spelled_bass) = match s.rsplit_once('/') {
+            Some((name, bass)) => (name, Some(bass.parse::<SpelledNote>()?)),
+            None => (s, None),
+        };
+
+        //The root is a letter followed by any number of sharps and flats
+        let root_length = name
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| !matches!(c, 'b' | '#' | '♭' | '♯'))
+            .map(|(i, _)| i)
+            .unwrap_or(name.len());
+
+        let spelled_root = name[..root_length].parse::<SpelledNote>()?;
+        let suffix = &name[root_length..];
+        let chord = Chord::from_symbol(suffix)
+            .ok_or_else(|| format!("'{suffix}' is not a chord symbol in '{s}'"))?;
+
+        Ok(Self {
+            root: spelled_root.note(),
+            chord,
+            bass: spelled_bass.map(|b| b.note()),
+            spelled_root: Some(spelled_root),
+            spelled_bass,
+        })
+    }
+}
+
+impl TryFrom<String> for ChordSymbol {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ChordSymbol> for String {
+    fn from(symbol: ChordSymbol) -> Self {
+        symbol.to_string()
+    }
+}
+
+/// How to choose between the readings of an ambiguous set of notes, such as Am7/C and C6
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum ChordPreference {
+    /// Prefer root position, then simpler chords
+    #[default]
+    Simplest,
+    /// Prefer chords whose root is in the major scale of this key, then the simplest reading
+    Key(Note),
+}
+
+impl ChordPreference {
+    const MAJOR_SCALE: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+    /// Lower ranks are preferred
+    pub fn rank(&self, analysis: &ChordAnalysis) -> (bool, Inversion, (usize, u8)) {
+        let outside_key = match self {
+            Self::Simplest => false,
+            Self::Key(tonic) => {
+                !Self::MAJOR_SCALE.contains(&((analysis.root.0 + 12 - tonic.0) % 12))
+            }
+        };
+
+        (outside_key, analysis.inversion, analysis.chord.complexity())
+    }
+}
+
+/// What an objective will accept.
+/// In level files this is written as `Some(Major)` or `None`, as a chord symbol like `"Am7"` or `"C/E"`,
+/// or as `(chord: Some(Major), inversion: Some(First))`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize)]
+pub struct ChordFilter {
+    /// None accepts any chord
+    pub chord: Option<Chord>,
+    /// None accepts any inversion
+    pub inversion: Option<Inversion>,
+    /// None accepts any root
+    pub root: Option<Note>,
+}
+
+impl ChordFilter {
+    pub fn matches(&self, analysis: &ChordAnalysis) -> bool {
+        self.chord.map(|c| c == analysis.chord).unwrap_or(true)
+            && self
+                .inversion
+                .map(|i| i == analysis.inversion)
+                .unwrap_or(true)
+            && self.root.map(|r| r == analysis.root).unwrap_or(true)
+    }
+}
+
+impl From<Option<Chord>> for ChordFilter {
+    fn from(chord: Option<Chord>) -> Self {
+        Self {
+            chord,
+            ..Default::default()
+        }
+    }
+}
+
+/// The symbol's root and chord are required. Any inversion is accepted unless the symbol has a bass note
+impl TryFrom<ChordSymbol> for ChordFilter {
+    type Error = String;
+
+    fn try_from(symbol: ChordSymbol) -> Result<Self, Self::Error> {
+        let inversion = match symbol.bass {
+            Some(bass) => Some(
+                ChordAnalysis::new(symbol.root, symbol.chord, bass)
+                    .ok_or_else(|| format!("{bass} is not a note of {symbol}"))?
+                    .inversion,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            chord: Some(symbol.chord),
+            inversion,
+            root: Some(symbol.root),
+        })
+    }
+}
+
+impl From<Chord> for ChordFilter {
+    fn from(chord: Chord) -> Self {
+        Some(chord).into()
+    }
+}
+
+impl std::fmt::Display for ChordFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(root) = self.root {
+            write!(f, "{root} ")?;
+        }
+        match self.chord {
+            Some(chord) => write!(f, "{}", chord.nice_name())?,
+            None => write!(f, "any")?,
+        }
+        if let Some(inversion) = self.inversion {
+            write!(f, " {inversion}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChordFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Default, Deserialize)]
+        #[serde(default)]
+        struct Fields {
+            chord: Option<Chord>,
+            inversion: Option<Inversion>,
+            root: Option<Note>,
+        }
+
+        struct FilterVisitor;
+
+        impl<'de> Visitor<'de> for FilterVisitor {
+            type Value = ChordFilter;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(
+                    f,
+                    "an optional chord, a chord symbol, or a struct with a chord and an inversion"
+                )
+            }
+
+            fn visit_none<E>(self) -> Result<Self::Value, E> {
+                Ok(ChordFilter::default())
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E> {
+                Ok(ChordFilter::default())
+            }
+
+            fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+                Ok(Chord::deserialize(d)?.into())
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Self::Value, E> {
+                s.parse::<ChordSymbol>()
+                    .and_then(ChordFilter::try_from)
+                    .map_err(E::custom)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let fields =
+                    Fields::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+                Ok(ChordFilter {
+                    chord: fields.chord,
+                    inversion: fields.inversion,
+                    root: fields.root,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(FilterVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::Cluster;
+
+    #[test]
+    fn chord_symbols_round_trip() {
+        for chord in Chord::iter() {
+            for root in [
+                "C", "C#", "Db", "D", "Eb", "D#", "E", "F", "F#", "Gb", "G", "G#", "Ab", "A", "A#",
+                "Bb", "B",
+            ] {
+                let text = format!("{root}{}", chord.symbol());
+                let symbol: ChordSymbol = text.parse().unwrap();
+                assert_eq!(symbol.chord, chord, "{text}");
+                assert_eq!(symbol.to_string(), text);
+            }
+        }
+    }
+
+    #[test]
+    fn chord_symbol_aliases_round_trip_to_the_symbol() {
+        for chord in Chord::iter() {
+            for alias in chord.aliases() {
+                let text = format!("C{alias}");
+                let symbol: ChordSymbol = text.parse().unwrap();
+                assert_eq!(symbol.chord, chord, "{text}");
+                assert_eq!(symbol.to_string(), format!("C{}", chord.symbol()));
+            }
+        }
+    }
+
+    #[test]
+    fn slash_chords_round_trip() {
+        for text in [
+            "C/E", "Am7/G", "G7/B", "Dm/F", "C/F#", "D/F#", "F#m/C#", "E/G#", "Gb/Bb", "Ab7/Gb",
+            "Db/Ab", "Bbmaj7/D",
+        ] {
+            assert_eq!(text.parse::<ChordSymbol>().unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn chord_symbols_keep_their_spelling() {
+        let sharp: ChordSymbol = "F#m".parse().unwrap();
+        let flat: ChordSymbol = "Gbm".parse().unwrap();
+        assert_eq!(sharp.root, flat.root);
+        assert_eq!(sharp.spell_note(Note::A, None).to_string(), "A");
+        assert_eq!(flat.spell_note(Note::A, None).to_string(), "Bbb");
+    }
+
+    #[test]
+    fn ma9_is_major_ninth() {
+        let parse = |s: &str| s.parse::<ChordSymbol>().unwrap().chord;
+        assert_eq!(parse("Cma9"), Chord::Major9);
+        assert_eq!(parse("Cma7"), Chord::Major7);
+        assert_eq!(parse("Cmadd9"), Chord::MinorAdd9);
+    }
+
+    fn inversion(chord: Chord, bass: Note) -> Option<Inversion> {
+        ChordAnalysis::new(Note::C, chord, bass).map(|a| a.inversion)
+    }
+
+    #[test]
+    fn extensions_in_the_bass_are_inverted_by_their_degree() {
+        assert_eq!(inversion(Chord::Add9, Note::D), Some(Inversion::Fourth));
+        assert_eq!(
+            inversion(Chord::Dominant13, Note::A),
+            Some(Inversion::Sixth)
+        );
+        assert_eq!(
+            inversion(Chord::Dominant11, Note::F),
+            Some(Inversion::Fifth)
+        );
+        assert_eq!(
+            inversion(Chord::Dominant7Sharp9, Note::EB),
+            Some(Inversion::Fourth)
+        );
+        assert_eq!(
+            inversion(Chord::Dominant13, Note::BB),
+            Some(Inversion::Third)
+        );
+    }
+
+    #[test]
+    fn stand_ins_are_inverted_like_the_notes_they_replace() {
+        assert_eq!(
+            inversion(Chord::Suspended2, Note::D),
+            Some(Inversion::First)
+        );
+        assert_eq!(
+            inversion(Chord::Dominant7Sus4, Note::F),
+            Some(Inversion::First)
+        );
+        assert_eq!(inversion(Chord::Major6, Note::A), Some(Inversion::Third));
+        assert_eq!(
+            inversion(Chord::Diminished7, Note::A),
+            Some(Inversion::Third)
+        );
+        assert_eq!(inversion(Chord::Major, Note::D), None);
+    }
+
+    fn cluster(notes: &[Note]) -> Cluster {
+        Cluster {
+            pitches: notes.iter().map(|&n| Pitch::new(n, 4)).collect(),
+        }
+    }
+
+    #[test]
+    fn c6_is_preferred_to_am7_over_c() {
+        let readings = cluster(&[Note::C, Note::E, Note::G, Note::A])
+            .get_ranked_chords(ChordPreference::Simplest)
+            .into_iter()
+            .map(|a| a.to_string())
+            .collect_vec();
+        assert_eq!(readings, vec!["C6", "Am7/C"]);
+    }
+
+    #[test]
+    fn diminished_sevenths_have_four_readings() {
+        let readings = cluster(&[Note::C, Note::EB, Note::GB, Note::A])
+            .get_ranked_chords(ChordPreference::Simplest)
+            .into_iter()
+            .map(|a| a.to_string())
+            .collect_vec();
+        assert_eq!(readings.len(), 4);
+        assert_eq!(readings[0], "Cdim7");
+        assert!(readings.contains(&"Adim7/C".to_string()));
+    }
+
+    #[test]
+    fn simpler_chords_rank_first_whatever_order_they_are_declared_in() {
+        let rank = |chord| {
+            ChordPreference::Simplest.rank(&ChordAnalysis::new(Note::C, chord, Note::C).unwrap())
+        };
+        assert!(rank(Chord::Power) < rank(Chord::Minor));
+        assert!(rank(Chord::Major6) < rank(Chord::Diminished7));
+        assert!(rank(Chord::Dominant7) < rank(Chord::Major7));
+
+        for chord in Chord::iter() {
+            for other in Chord::iter() {
+                if chord.intervals().len() < other.intervals().len() {
+                    assert!(rank(chord) < rank(other), "{chord:?} and {other:?}");
+                }
+                if chord != other {
+                    assert_ne!(chord.complexity(), other.complexity());
+                }
+            }
+        }
+    }
+}
@@ -4,7 +4,14 @@ use itertools::Itertools;
 use smallvec::ToSmallVec;
 
 use crate::{
-    cluster::*, components::*, events::*, objective::Objective, BIG_TEXT_COLOR, SMALL_TEXT_COLOR,
+    chord::{ChordPreference, ChordSymbol},
+    cluster::*,
+    components::*,
+    events::*,
+    level::LevelKey,
+    objective::Objective,
+    spelling::SpelledNote,
+    BIG_TEXT_COLOR, SMALL_TEXT_COLOR,
 };
 
 pub struct ChordTextPlugin;
@@ -42,12 +49,14 @@ fn change_chord_text(
     interacting_objectives: Query<(&Objective, &Interactable)>,
     interacting_changed_objectives: Query<&Objective, Changed<Interactable>>,
     interacting_orbs: Query<(With<Orb>, &Interactable)>,
+    preference: Res<ChordPreference>,
+    key: Res<LevelKey>,
 ) {
     let new_text_option: Option<(String, String)> = if let Some(ev) = er.iter().last() {
         //info!("NPCE");
         //something has changed. Reset chord text
-        let notes = ev
-            .notes
+        let pitches = ev
+            .pitches
             .iter()
             .sorted()
             .dedup()
@@ -55,17 +64,23 @@ fn change_chord_text(
             .collect_vec()
             .to_smallvec();
 
-        let cluster = Cluster { notes };
+        let cluster = Cluster { pitches };
 
-        let chord_option = cluster.get_chord();
-
-        if let Some((root, chord)) = chord_option {
+        if let Some(analysis) = cluster.get_chord(*preference) {
             Some((
-                format!("{} {}", root.get_name(), chord.nice_name()),
-                cluster.get_notes_text(),
+                ChordSymbol::from(analysis).spelled_name(key.0),
+                analysis
+                    .spell_notes(&cluster.notes(), key.0)
+                    .iter()
+                    .join(" "),
             ))
         } else {
-            Some((cluster.get_notes_text(), "".to_string()))
+            let notes = cluster
+                .notes()
+                .into_iter()
+                .map(|n| SpelledNote::in_key(n, key.0))
+                .join(" ");
+            Some((notes, "".to_string()))
         }
     } else if !interacting_changed_objectives.is_empty()
         && interacting_orbs
@@ -76,11 +91,16 @@ fn change_chord_text(
     {
         //info!("ICO");
         if let Some(obj) = interacting_objectives.iter().find(|x| x.1.interacting) {
-            if let Some(chord) = obj.0.filter {
-                let intervals = chord.intervals().iter().map(|x| x.to_string()).join(" ");
-                Some((chord.nice_name().to_string(), intervals))
+            if let Some(chord) = obj.0.filter.chord {
+                let intervals = chord
+                    .intervals()
+                    .iter()
+                    .sorted()
+                    .map(|x| x.to_string())
+                    .join(" ");
+                Some((obj.0.filter.to_string(), intervals))
             } else {
-                Some(("any".to_string(), "any".to_string()))
+                Some((obj.0.filter.to_string(), "any".to_string()))
             }
         } else {
             Some(("".to_string(), "".to_string()))
@@ -1,31 +1,40 @@
 use std::fmt::Debug;
+use std::str::FromStr;
 
 use bevy::prelude::Color;
 use bevy_prototype_lyon::prelude::*;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use smallvec::*;
 
 use crate::chord::*;
+use crate::spelling::SpelledNote;
 use crate::*;
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Cluster {
-    pub notes: SmallVec<[Note; 4]>,
+    pub pitches: SmallVec<[Pitch; 4]>,
 }
 
-impl From<Note> for Cluster {
-    fn from(val: Note) -> Self {
+impl From<Pitch> for Cluster {
+    fn from(val: Pitch) -> Self {
         Cluster {
-            notes: smallvec![val],
+            pitches: smallvec![val],
         }
     }
 }
 
+impl From<Note> for Cluster {
+    fn from(val: Note) -> Self {
+        Pitch::from(val).into()
+    }
+}
+
 impl Cluster {
     pub fn get_draw_mode(&self, playing: bool) -> DrawMode {
-        if self.notes.len() == 1 {
+        if self.pitches.len() == 1 {
             if playing {
-                DrawMode::Fill(FillMode::color(self.notes[0].get_dark_color()))
+                DrawMode::Fill(FillMode::color(self.pitches[0].note().get_dark_color()))
             } else {
                 DrawMode::Fill(FillMode::color(Color::NONE))
             }
@@ -36,48 +45,66 @@ impl Cluster {
         }
     }
 
-    pub fn get_chord(&self) -> Option<(Note, Chord)> {
-        if self.notes.len() == 3 {
-            let sorted_notes = self.notes.iter().map(|&x| x.0).sorted();
-            let arr: [u8; 3] = sorted_notes.collect_vec().try_into().unwrap();
+    /// The pitch classes of the pitches, in the same order
+    pub fn notes(&self) -> Vec<Note> {
+        self.pitches.iter().map(|p| p.note()).collect_vec()
+    }
 
-            for i in 0..3 {
-                let a1 = Self::permute(arr, i).to_vec();
-                let chord_option = Chord::all().get(&a1);
+    /// The pitch class of the lowest pitch
+    pub fn bass(&self) -> Option<Note> {
+        self.pitches.iter().min().map(|p| p.note())
+    }
 
-                if let Some(chord) = chord_option {
-                    return Some((self.notes[i], *chord));
-                }
-            }
-        } else if self.notes.len() == 4 {
-            let sorted_notes = self.notes.iter().map(|&x| x.0).sorted();
-            let arr: [u8; 4] = sorted_notes.collect_vec().try_into().unwrap();
+    /// Every way of reading this cluster as a chord, one for each note which could be the root
+    pub fn get_chords(&self) -> Vec<ChordAnalysis> {
+        let bass = match self.bass() {
+            Some(bass) => bass,
+            None => return vec![],
+        };
+        let sorted_notes = self
+            .pitches
+            .iter()
+            .map(|p| p.note().0)
+            .sorted()
+            .dedup()
+            .collect_vec();
 
-            for i in 0..4 {
-                let a1 = Self::permute(arr, i).to_vec();
-                let chord_option = Chord::all().get(&a1);
+        (0..sorted_notes.len())
+            .filter_map(|i| {
+                let chord = Chord::all().get(&Self::permute(&sorted_notes, i))?;
+                ChordAnalysis::new(Note(sorted_notes[i]), *chord, bass)
+            })
+            .collect_vec()
+    }
 
-                if let Some(chord) = chord_option {
-                    return Some((self.notes[i], *chord));
-                }
-            }
-        }
+    /// Every reading of this cluster as a chord, best first
+    pub fn get_ranked_chords(&self, preference: ChordPreference) -> Vec<ChordAnalysis> {
+        self.get_chords()
+            .into_iter()
+            .sorted_by_key(|analysis| preference.rank(analysis))
+            .collect_vec()
+    }
 
-        None
+    /// The preferred reading of this cluster as a chord
+    pub fn get_chord(&self, preference: ChordPreference) -> Option<ChordAnalysis> {
+        self.get_chords()
+            .into_iter()
+            .min_by_key(|analysis| preference.rank(analysis))
     }
 
-    // pub fn get_chord_name(&self) -> Option<String> {
-    //     if let Some((root, chord)) = self.get_chord() {
-    //         return Some(format!("{}{}", root.get_name(), chord));
-    //     }
-    //     None
-    // }
+    /// Whether this cluster would complete an objective with this filter, when read as any chord
+    pub fn meets_filter(&self, filter: ChordFilter) -> bool {
+        self.get_chords()
+            .iter()
+            .any(|analysis| filter.matches(analysis))
+    }
 
-    fn permute<const L: usize>(notes: [u8; L], index: usize) -> [u8; L] {
-        let mut new_notes = notes;
+    /// The intervals of the sorted notes above the note at this index
+    fn permute(notes: &[u8], index: usize) -> Vec<u8> {
+        let mut new_notes = notes.to_vec();
         new_notes.rotate_left(index);
-        for i in 0..L {
-            new_notes[i] = ((new_notes[i] + 12) - notes[index]) % 12;
+        for n in new_notes.iter_mut() {
+            *n = ((*n + 12) - notes[index]) % 12;
         }
 
         new_notes
@@ -86,23 +113,23 @@ impl Cluster {
 
 impl Cluster {
     pub fn get_notes_text(&self) -> String {
-        self.notes.iter().map(|x| x.get_name()).join(" ")
+        self.pitches.iter().join(" ")
     }
 
     ///Combine many clusters
     pub fn combine(clusters: &Vec<Self>) -> Vec<Cluster> {
-        let all_notes = clusters.iter().flat_map(|x| x.notes.clone()).counts();
+        let all_pitches = clusters.iter().flat_map(|x| x.pitches.clone()).counts();
 
         let mut all_clusters = Vec::<Cluster>::new();
 
-        let mut main_cluster = Vec::<Note>::new();
+        let mut main_cluster = Vec::<Pitch>::new();
 
-        for (&note, &count) in all_notes.iter() {
+        for (pitch, count) in all_pitches.into_iter().sorted() {
             if count == 1 {
-                main_cluster.push(note);
+                main_cluster.push(pitch);
             } else {
                 for _ in 0..count {
-                    all_clusters.push(note.into());
+                    all_clusters.push(pitch.into());
                 }
             }
         }
@@ -113,15 +140,25 @@ impl Cluster {
             }
         } else {
             all_clusters.push(Cluster {
-                notes: main_cluster.to_smallvec(),
+                pitches: main_cluster.to_smallvec(),
             })
         }
 
         all_clusters
     }
+
+    ///Split a cluster into its individual notes. Returns None if there is only one note
+    pub fn deconstruct(&self) -> Option<Vec<Cluster>> {
+        if self.pitches.len() > 1 {
+            Some(self.pitches.iter().map(|&p| p.into()).collect_vec())
+        } else {
+            None
+        }
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub struct Note(pub u8);
 
 const fn create_note(i: usize) -> Note {
@@ -152,41 +189,8 @@ impl Note {
 impl Note {
     pub const ALL_NOTES: [Note; 12] = array_const_fn_init::array_const_fn_init![create_note; 12];
 
-    pub fn get_frequency(self) -> f32 {
-        const FREQUENCY_C: f32 = 261.62;
-        match self.0 % 12 {
-            0 => FREQUENCY_C * 1.0,
-            1 => FREQUENCY_C * 16. / 15.,
-            2 => FREQUENCY_C * 9. / 8.,
-            3 => FREQUENCY_C * 6. / 5.,
-            4 => FREQUENCY_C * 5. / 4.,
-            5 => FREQUENCY_C * 4. / 3.,
-            6 => FREQUENCY_C * 64. / 45.,
-            7 => FREQUENCY_C * 3. / 2.,
-            8 => FREQUENCY_C * 8. / 5.,
-            9 => FREQUENCY_C * 5. / 3.,
-            10 => FREQUENCY_C * 16. / 9.,
-            11 => FREQUENCY_C * 15. / 8.,
-            _ => unimplemented!(),
-        }
-    }
+    /// The name of the note written with flats. Use `SpelledNote` to spell it to suit a chord or key
     pub fn get_name(self) -> &'static str {
-        // match self.0 % 12 {
-        //     0 => "C",
-        //     1 => "C#",
-        //     2 => "D",
-        //     3 => "D#",
-        //     4 => "E",
-        //     5 => "F",
-        //     6 => "F#",
-        //     7 => "G",
-        //     8 => "G#",
-        //     9 => "A",
-        //     10 => "A#",
-        //     11 => "B",
-        //     _ => unimplemented!(),
-        // }
-
         match self.0 % 12 {
             0 => "C",
             1 => "Db",
@@ -225,8 +229,156 @@ impl Note {
     }
 }
 
+impl FromStr for Note {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.trim().chars();
+
+        let natural = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => return Err(format!("'{s}' is not a note name")),
+        };
+
+        let mut value: i32 = natural;
+        for accidental in chars {
+            match accidental {
+                'b' | '♭' => value -= 1,
+                '#' | '♯' => value += 1,
+                _ => return Err(format!("'{s}' is not a note name")),
+            }
+        }
+
+        Ok(Note(value.rem_euclid(12) as u8))
+    }
+}
+
+impl TryFrom<String> for Note {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Note> for String {
+    fn from(note: Note) -> Self {
+        note.get_name().to_string()
+    }
+}
+
 impl Debug for Note {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.get_name())
     }
 }
+
+/// A note in a particular octave, numbered as in MIDI so middle C is 60
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pitch(pub u8);
+
+impl Pitch {
+    pub const MIDDLE_C: Self = Pitch(60);
+    /// The octave notes are in if no octave is given, the one starting at middle C
+    pub const DEFAULT_OCTAVE: i8 = 4;
+
+    /// The note in an octave numbered in scientific pitch notation, so middle C is C4
+    pub fn new(note: Note, octave: i8) -> Self {
+        let pitch = (octave as i16 + 1) * 12 + (note.0 % 12) as i16;
+        Pitch(pitch.clamp(0, 127) as u8)
+    }
+
+    /// The pitch class, which is what chords are made of
+    pub fn note(self) -> Note {
+        Note(self.0 % 12)
+    }
+
+    /// The octave in scientific pitch notation, so middle C is in octave 4
+    pub fn octave(self) -> i8 {
+        (self.0 / 12) as i8 - 1
+    }
+
+    /// The note's colour, lighter for higher octaves and darker for lower ones
+    pub fn get_color(self) -> Color {
+        let lightness = 0.8 + 0.1 * (self.octave() - Self::DEFAULT_OCTAVE) as f32;
+        match self.note().get_color() {
+            Color::Hsla {
+                hue,
+                saturation,
+                alpha,
+                ..
+            } => Color::Hsla {
+                hue,
+                saturation,
+                lightness: lightness.clamp(0.3, 0.95),
+                alpha,
+            },
+            color => color,
+        }
+    }
+}
+
+/// Notes without an octave are in the octave starting at middle C
+impl From<Note> for Pitch {
+    fn from(note: Note) -> Self {
+        Pitch::new(note, Self::DEFAULT_OCTAVE)
+    }
+}
+
+impl std::fmt::Display for Pitch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.note(), self.octave())
+    }
+}
+
+impl Debug for Pitch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// A note name followed by an optional octave, e.g. `"Eb"` or `"Eb3"`
+impl FromStr for Pitch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, octave) = match s.find(|c: char| c.is_ascii_digit() || c == '-') {
+            Some(index) => {
+                let octave = s[index..]
+                    .parse::<i8>()
+                    .map_err(|_| format!("'{s}' does not have a valid octave"))?;
+                (&s[..index], octave)
+            }
+            None => (s, Self::DEFAULT_OCTAVE),
+        };
+
+        let pitch = (octave as i16 + 1) * 12 + name.parse::<SpelledNote>()?.semitones() as i16;
+        if (0..=127).contains(&pitch) {
+            Ok(Pitch(pitch as u8))
+        } else {
+            Err(format!("'{s}' is too high or too low"))
+        }
+    }
+}
+
+impl TryFrom<String> for Pitch {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Pitch> for String {
+    fn from(pitch: Pitch) -> Self {
+        pitch.to_string()
+    }
+}
@@ -2,6 +2,7 @@ use bevy::prelude::*;
 use itertools::Itertools;
 
 use crate::cluster::*;
+use crate::solver::MAX_COMBINED;
 use crate::*;
 
 pub struct CombinePlugin;
@@ -17,11 +18,24 @@ fn combine_orbs(
     mut er_combine: EventReader<CombineEvent>,
     orbs: Query<(Entity, &Transform, &Orb, &Children)>,
     note_circles: Query<(Entity, &NoteCircle, &GlobalTransform)>,
+    board: Query<(&Transform, &Orb, Option<&CompletingObjective>)>,
+    mut stats: ResMut<LevelStats>,
+    mut history: ResMut<UndoHistory>,
+    time: Res<Time>,
 ) {
     for ev in er_combine.iter() {
-        let groups = ev.0.iter().filter_map(|&e| orbs.get(e).ok()).collect_vec();
+        let groups =
+            ev.0.iter()
+                .filter_map(|&e| orbs.get(e).ok())
+                .take(MAX_COMBINED)
+                .collect_vec();
 
         if groups.len() > 1 {
+            history.record(time.seconds_since_startup(), || {
+                BoardState::capture(&board, stats.moves)
+            });
+            stats.moves += 1;
+
             let mut note_circles = groups
                 .iter()
                 .flat_map(|x| x.3)
@@ -1,10 +1,11 @@
 use bevy::prelude::*;
 
 use crate::{
-    cluster::{Cluster, Note},
+    cluster::{Cluster, Pitch},
     events::*,
 };
 
+/// Restarts the current level when clicked
 #[derive(Component)]
 pub struct RestartButton {}
 
@@ -24,7 +25,7 @@ pub struct Interactable {
 
 #[derive(Component)]
 pub struct NoteCircle {
-    pub note: Note,
+    pub pitch: Pitch,
 }
 
 #[derive(Component)]
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::generator::LevelParameters;
+use crate::*;
+
+const DAILY_RECORDS_KEY: &str = "daily";
+/// Keeps daily seeds away from the seeds used for generated campaign levels
+const DAILY_SEED_OFFSET: u64 = 1 << 32;
+
+pub struct DailyPlugin;
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DailyRecords::load())
+            .add_system(handle_mode_buttons)
+            .add_system(record_daily_completion);
+    }
+}
+
+/// The best result for each daily challenge the player has completed, by day
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct DailyRecords {
+    pub days: BTreeMap<u32, LevelRecord>,
+}
+
+impl DailyRecords {
+    pub fn load() -> Self {
+        storage::load(DAILY_RECORDS_KEY)
+            .and_then(|s| ron::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Ok(s) = ron::to_string(self) {
+            storage::save(DAILY_RECORDS_KEY, &s);
+        }
+    }
+}
+
+/// The number of days since 1970-01-01 in UTC, so everyone gets the same puzzle on the same day
+pub fn today() -> u32 {
+    #[cfg(target_arch = "wasm32")]
+    let millis = js_sys::Date::now();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as f64)
+        .unwrap_or_default();
+
+    (millis / 86_400_000.).floor() as u32
+}
+
+/// Format a day number from `today` as yyyy-mm-dd
+pub fn date_string(day: u32) -> String {
+    //See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = day as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let d = day_of_year - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = year_of_era + era * 400 + if m <= 2 { 1 } else { 0 };
+
+    format!("{y}-{m:02}-{d:02}")
+}
+
+/// The daily challenge gets harder through the week, from Monday to Sunday
+pub fn daily_level(day: u32) -> GameLevel {
+    //1970-01-01 was a Thursday
+    let weekday = (day + 3) % 7;
+    let mut level = LevelParameters::for_difficulty(4 + 2 * weekday as usize)
+        .generate(day as u64 + DAILY_SEED_OFFSET);
+    level.header = format!("Daily {}", date_string(day));
+    level
+}
+
+fn handle_mode_buttons(
+    mut commands: Commands,
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut game_mode: ResMut<GameMode>,
+    current_level: Res<CurrentLevel>,
+    level_entities: Query<Entity, Or<(With<Objective>, With<Orb>)>>,
+    mut level_text: Query<(Entity, &LevelText, &mut Text)>,
+    mut ew_go_to_level: EventWriter<GoToLevelEvent>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            MenuButton::Daily => {
+                let day = today();
+                *game_mode = GameMode::Daily { day };
+
+                clear_level(&mut commands, &level_entities);
+                start_level(&mut commands, &daily_level(day), &mut level_text);
+            }
+            MenuButton::Campaign => ew_go_to_level.send(GoToLevelEvent(current_level.0)),
+            _ => {}
+        }
+    }
+}
+
+fn record_daily_completion(
+    mut commands: Commands,
+    mut er: EventReader<LevelCompletedEvent>,
+    mut records: ResMut<DailyRecords>,
+    mut level_text: Query<(Entity, &LevelText, &mut Text)>,
+) {
+    for ev in er.iter() {
+        if let GameMode::Daily { day } = ev.game_mode {
+            let record = LevelRecord {
+                moves: ev.moves,
+                seconds: ev.seconds,
+            };
+
+            let best = records.days.entry(day).or_insert(record);
+            if record.is_better_than(best) {
+                *best = record;
+            }
+            let best = *best;
+            records.save();
+
+            let seconds = ev.seconds as u64;
+            set_level_text(
+                &mut commands,
+                &mut level_text,
+                "Daily complete!",
+                &format!(
+                    "{} moves in {}:{:02} (best {})",
+                    ev.moves,
+                    seconds / 60,
+                    seconds % 60,
+                    best.moves
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day_zero_is_the_unix_epoch() {
+        assert_eq!(date_string(0), "1970-01-01");
+        assert_eq!(date_string(20744), "2026-10-18");
+        assert_eq!(date_string(24855), "2038-01-19");
+    }
+
+    #[test]
+    fn years_roll_over_at_new_year() {
+        assert_eq!(date_string(364), "1970-12-31");
+        assert_eq!(date_string(365), "1971-01-01");
+        assert_eq!(date_string(10956), "1999-12-31");
+        assert_eq!(date_string(10957), "2000-01-01");
+    }
+
+    #[test]
+    fn leap_years_have_a_29th_of_february() {
+        assert_eq!(date_string(59), "1970-03-01");
+        assert_eq!(date_string(789), "1972-02-29");
+        assert_eq!(date_string(790), "1972-03-01");
+        assert_eq!(date_string(19782), "2024-02-29");
+
+        //Centuries are only leap years every 400 years
+        assert_eq!(date_string(11016), "2000-02-29");
+        assert_eq!(date_string(11017), "2000-03-01");
+        assert_eq!(date_string(47540), "2100-02-28");
+        assert_eq!(date_string(47541), "2100-03-01");
+    }
+}
@@ -22,11 +22,20 @@ fn check_for_deconstructors(
     orbs: Query<(Entity, &Transform, &Orb, &Children)>,
     note_circles: Query<(Entity, &NoteCircle, &GlobalTransform)>,
     deconstructors: Query<&Deconstructor>,
+    board: Query<(&Transform, &Orb, Option<&CompletingObjective>)>,
+    mut stats: ResMut<LevelStats>,
+    mut history: ResMut<UndoHistory>,
+    time: Res<Time>,
 ) {
     for ev in er_dragend.iter() {
         if deconstructors.contains(ev.target) {
             if let Ok((e, t, o, children)) = orbs.get(ev.dragged) {
-                if o.cluster.notes.len() > 1 {
+                if let Some(new_clusters) = o.cluster.deconstruct() {
+                    history.record(time.seconds_since_startup(), || {
+                        BoardState::capture(&board, stats.moves)
+                    });
+                    stats.moves += 1;
+
                     let rangex = (t.translation.x - SHAPE_SIZE).max(-WINDOW_WIDTH / 2.)
                         ..(t.translation.x + SHAPE_SIZE).min(WINDOW_WIDTH / 2.);
                     let rangey = (t.translation.y - SHAPE_SIZE).max(-WINDOW_HEIGHT / 2.)
@@ -37,11 +46,11 @@ fn check_for_deconstructors(
                         .filter_map(|&e| note_circles.get(e).ok())
                         .collect_vec();
 
-                    for &note in o.cluster.notes.iter() {
+                    for cluster in new_clusters {
                         create_orb_near(
                             &mut commands,
                             SHAPE_SIZE,
-                            note.into(),
+                            cluster,
                             rangex.clone(),
                             rangey.clone(),
                             &mut note_circles,
@@ -22,6 +22,7 @@ fn drag_end(
 
     mut er_drag_end: EventReader<DragEndEvent>,
     mut dragged: Query<(Entity, &Draggable, &Dragged, &mut Transform)>,
+    undragged: Query<&Transform, Without<Dragged>>,
 
     mut ew_combine: EventWriter<CombineEvent>,
     mut ew_deconstruct: EventWriter<DragEndWithIntersection>,
@@ -31,16 +32,25 @@ fn drag_end(
         dragged
             .iter_mut()
             .filter(|f| f.2.drag_source == event.drag_source)
-            .for_each(|(entity, _, _, _)| {
+            .for_each(|(entity, _, _, transform)| {
+                let distance = |e: &Entity| {
+                    undragged
+                        .get(*e)
+                        .map(|t| t.translation.distance(transform.translation))
+                        .unwrap_or(f32::MAX)
+                };
+                //The dragged entity first, then what it touches from nearest to furthest
                 let all_contacts = std::iter::once(entity)
                     .chain(
                         rapier_context
                             .contacts_with(entity)
                             .filter(|x| x.has_any_active_contacts())
-                            .flat_map(|x| [x.collider1(), x.collider2()]),
+                            .flat_map(|x| [x.collider1(), x.collider2()])
+                            .filter(|&e| e != entity)
+                            .sorted()
+                            .dedup()
+                            .sorted_by(|a, b| distance(a).total_cmp(&distance(b))),
                     )
-                    .sorted()
-                    .dedup()
                     .collect_vec();
 
                 if all_contacts.len() > 1 {
@@ -1,4 +1,5 @@
 use crate::cluster::*;
+use crate::level::GameMode;
 use bevy::prelude::*;
 
 pub struct EventsPlugin;
@@ -11,15 +12,49 @@ impl Plugin for EventsPlugin {
             .add_event::<NewGameEvent>()
             .add_event::<CombineEvent>()
             .add_event::<DragEndWithIntersection>()
-            .add_event::<NotesPlayingChangedEvent>();
+            .add_event::<NotesPlayingChangedEvent>()
+            .add_event::<ObjectiveCompletedEvent>()
+            .add_event::<LevelCompletedEvent>()
+            .add_event::<GoToLevelEvent>()
+            .add_event::<UndoEvent>()
+            .add_event::<RedoEvent>();
     }
 }
 
 #[derive(Debug)]
 pub struct NotesPlayingChangedEvent {
-    pub notes: Vec<Note>,
+    pub pitches: Vec<Pitch>,
 }
 
+/// An orb has been dropped on an objective and completed it
+#[derive(Debug)]
+pub struct ObjectiveCompletedEvent {
+    pub cluster: Cluster,
+}
+
+#[derive(Debug)]
+pub struct LevelCompletedEvent {
+    pub game_mode: GameMode,
+    /// The campaign level, if the game mode is campaign
+    pub level: usize,
+    pub moves: usize,
+    pub seconds: f64,
+    /// The cluster which completed each objective, from left to right
+    pub chords: Vec<Cluster>,
+}
+
+/// Play this campaign level, whatever is currently being played
+#[derive(Debug)]
+pub struct GoToLevelEvent(pub usize);
+
+/// Put the board back to how it was before the last combine or deconstruct
+#[derive(Debug)]
+pub struct UndoEvent;
+
+/// Put back the last change which was undone
+#[derive(Debug)]
+pub struct RedoEvent;
+
 #[derive(Debug)]
 pub struct DragStartEvent {
     pub drag_source: DragSource,
@@ -38,6 +73,7 @@ pub struct DragEndEvent {
     pub position: Option<Vec2>,
 }
 
+/// The dragged orb first, then whatever it was dropped touching from nearest to furthest
 #[derive(Debug)]
 pub struct CombineEvent(pub Vec<Entity>);
 
@@ -53,7 +89,6 @@ pub enum DragSource {
     Touch { id: u64 },
 }
 
+/// Start the current level again from scratch
 #[derive(Debug)]
-pub struct NewGameEvent {
-    pub box_count_change: i32,
-}
+pub struct NewGameEvent;
@@ -0,0 +1,327 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::cluster::*;
+use crate::*;
+
+pub struct FlourishPlugin;
+impl Plugin for FlourishPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlourishQueue>()
+            //Before the next level's instrument replaces this one's
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                queue_flourishes.after("check_for_completion"),
+            )
+            .add_system(play_flourishes);
+    }
+}
+
+/// How long the last note or chord of a flourish is held, in beats
+const FINAL_BEATS: f32 = 2.;
+
+/// The order the notes of a completed chord are played in
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ArpeggioPattern {
+    /// From the lowest note to the highest
+    #[default]
+    Up,
+    /// From the highest note to the lowest
+    Down,
+    /// Up to the highest note and back down again
+    UpAndDown,
+    /// Every note at once
+    Together,
+}
+
+impl ArpeggioPattern {
+    /// The pitches sounding on each beat
+    pub fn steps(&self, cluster: &Cluster) -> Vec<Vec<Pitch>> {
+        let up = cluster
+            .pitches
+            .iter()
+            .copied()
+            .sorted()
+            .dedup()
+            .collect_vec();
+        match self {
+            ArpeggioPattern::Up => up.into_iter().map(|p| vec![p]).collect(),
+            ArpeggioPattern::Down => up.into_iter().rev().map(|p| vec![p]).collect(),
+            ArpeggioPattern::UpAndDown => {
+                let down = up.iter().rev().skip(1).copied().collect_vec();
+                up.into_iter().chain(down).map(|p| vec![p]).collect()
+            }
+            ArpeggioPattern::Together => vec![up],
+        }
+    }
+}
+
+/// The music played when objectives and levels are completed
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlourishSettings {
+    pub enabled: bool,
+    /// Beats per minute. Each note of an arpeggio is one beat
+    pub tempo: f32,
+    pub pattern: ArpeggioPattern,
+    /// How many beats each chord of the level's progression is played for
+    pub chord_beats: f32,
+    /// The volume compared to the notes of the orbs
+    pub volume: f32,
+}
+
+impl Default for FlourishSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tempo: 300.,
+            pattern: ArpeggioPattern::default(),
+            chord_beats: 3.,
+            volume: 0.6,
+        }
+    }
+}
+
+impl FlourishSettings {
+    pub fn beat_seconds(&self) -> f64 {
+        60. / self.tempo.max(1.) as f64
+    }
+}
+
+/// A note of a flourish, which has its own drone so it doesn't disturb the notes of the orbs
+struct FlourishNote {
+    start: f64,
+    end: f64,
+    pitch: Pitch,
+    volume: f32,
+    instrument: Instrument,
+    control: Option<Arc<DroneControl>>,
+}
+
+/// The notes of flourishes waiting to be played or playing. Flourishes play one after another
+#[derive(Default)]
+pub struct FlourishQueue {
+    notes: Vec<FlourishNote>,
+    /// When the last flourish finishes, in seconds since startup
+    end: f64,
+}
+
+impl FlourishQueue {
+    /// Play each step in turn after whatever is already queued, holding the last one for longer
+    fn queue(
+        &mut self,
+        steps: Vec<Vec<Pitch>>,
+        beats: f32,
+        settings: &FlourishSettings,
+        now: f64,
+        instrument: Instrument,
+    ) {
+        let beat = settings.beat_seconds();
+        let mut start = self.end.max(now);
+        let mut end = start;
+        let last = steps.len().saturating_sub(1);
+
+        for (i, step) in steps.into_iter().enumerate() {
+            let held = if i == last {
+                FINAL_BEATS.max(beats)
+            } else {
+                beats
+            };
+            let length = beat * held as f64;
+            let volume = settings.volume / step.len().max(1) as f32;
+            end = start + length;
+            for pitch in step {
+                self.notes.push(FlourishNote {
+                    start,
+                    end,
+                    pitch,
+                    volume,
+                    instrument,
+                    control: None,
+                });
+            }
+            start += beat * beats as f64;
+        }
+
+        self.end = end;
+    }
+}
+
+fn queue_flourishes(
+    mut objective_events: EventReader<ObjectiveCompletedEvent>,
+    mut level_events: EventReader<LevelCompletedEvent>,
+    settings: Res<Settings>,
+    level_instrument: Res<LevelInstrument>,
+    time: Res<Time>,
+    mut queue: ResMut<FlourishQueue>,
+) {
+    let flourish = settings.flourish;
+    let now = time.seconds_since_startup();
+    let instrument = level_instrument.0.unwrap_or(settings.instrument);
+
+    for ev in objective_events.iter() {
+        if flourish.enabled {
+            let steps = flourish.pattern.steps(&ev.cluster);
+            queue.queue(steps, 1., &flourish, now, instrument);
+        }
+    }
+
+    for ev in level_events.iter() {
+        if flourish.enabled {
+            let steps = ev
+                .chords
+                .iter()
+                .map(|c| c.pitches.iter().copied().unique().collect_vec())
+                .collect_vec();
+            queue.queue(steps, flourish.chord_beats, &flourish, now, instrument);
+        }
+    }
+}
+
+fn play_flourishes(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    frequencies: Res<PitchFrequencies>,
+    audio: Option<Res<Audio<PitchDrone>>>,
+    mut drones: Option<ResMut<Assets<PitchDrone>>>,
+    mut queue: ResMut<FlourishQueue>,
+) {
+    let now = time.seconds_since_startup();
+
+    for note in queue.notes.iter_mut() {
+        if note.control.is_none() && note.start <= now {
+            let output = audio.as_deref().zip(drones.as_deref_mut());
+            let control = start_pitch_sound(note.pitch, &frequencies, note.instrument, output);
+            control.set_volume(settings.volume * note.volume);
+            note.control = Some(control);
+        }
+    }
+
+    queue.notes.retain(|note| match &note.control {
+        Some(control) if note.end <= now => {
+            control.stop();
+            false
+        }
+        _ => true,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pitches(names: &str) -> Vec<Pitch> {
+        names.split(' ').map(|p| p.parse().unwrap()).collect()
+    }
+
+    fn steps(pattern: ArpeggioPattern) -> Vec<Vec<Pitch>> {
+        //Out of order, with a repeated pitch
+        let cluster = Cluster {
+            pitches: pitches("E4 C4 G4 C5 E4").into_iter().collect(),
+        };
+        pattern.steps(&cluster)
+    }
+
+    fn one_at_a_time(names: &str) -> Vec<Vec<Pitch>> {
+        pitches(names).into_iter().map(|p| vec![p]).collect()
+    }
+
+    /// When each note of the queue starts and ends, in beats at the default tempo
+    fn timings(queue: &FlourishQueue) -> Vec<(f64, f64)> {
+        let beat = FlourishSettings::default().beat_seconds();
+        queue
+            .notes
+            .iter()
+            .map(|n| {
+                (
+                    (n.start / beat * 100.).round() / 100.,
+                    (n.end / beat * 100.).round() / 100.,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arpeggios_go_up_and_down() {
+        assert_eq!(steps(ArpeggioPattern::Up), one_at_a_time("C4 E4 G4 C5"));
+        assert_eq!(steps(ArpeggioPattern::Down), one_at_a_time("C5 G4 E4 C4"));
+        assert_eq!(
+            steps(ArpeggioPattern::UpAndDown),
+            one_at_a_time("C4 E4 G4 C5 G4 E4 C4")
+        );
+        assert_eq!(
+            steps(ArpeggioPattern::Together),
+            vec![pitches("C4 E4 G4 C5")]
+        );
+    }
+
+    #[test]
+    fn the_last_step_is_held() {
+        let settings = FlourishSettings::default();
+        let mut queue = FlourishQueue::default();
+        queue.queue(
+            one_at_a_time("C4 E4 G4"),
+            1.,
+            &settings,
+            0.,
+            Instrument::default(),
+        );
+
+        assert_eq!(
+            timings(&queue),
+            vec![(0., 1.), (1., 2.), (2., 2. + FINAL_BEATS as f64)]
+        );
+
+        //Chords which are already held longer than that aren't shortened
+        let mut queue = FlourishQueue::default();
+        queue.queue(
+            vec![pitches("C4 E4 G4")],
+            3.,
+            &settings,
+            0.,
+            Instrument::default(),
+        );
+        assert_eq!(timings(&queue), vec![(0., 3.); 3]);
+        assert!(queue.notes.iter().all(|n| n.volume == settings.volume / 3.));
+    }
+
+    #[test]
+    fn flourishes_play_one_after_another() {
+        let settings = FlourishSettings::default();
+        let beat = settings.beat_seconds();
+        let mut queue = FlourishQueue::default();
+
+        queue.queue(
+            one_at_a_time("C4 E4"),
+            1.,
+            &settings,
+            0.,
+            Instrument::default(),
+        );
+        queue.queue(
+            one_at_a_time("G4"),
+            1.,
+            &settings,
+            beat,
+            Instrument::default(),
+        );
+        assert_eq!(
+            timings(&queue),
+            vec![(0., 1.), (1., 3.), (3., 3. + FINAL_BEATS as f64)]
+        );
+
+        //Once the queue has finished, the next flourish starts straight away
+        let later = 10. * beat;
+        queue.queue(
+            one_at_a_time("C5"),
+            1.,
+            &settings,
+            later,
+            Instrument::default(),
+        );
+        assert_eq!(timings(&queue)[3], (10., 10. + FINAL_BEATS as f64));
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use bevy::render::once_cell::sync::OnceCell;
+use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::chord::{Chord, ChordFilter};
+use crate::cluster::{Note, Pitch};
+use crate::level::GameLevel;
+use crate::solver::{moves_to_combine, solve};
+
+/// How many random levels to try before settling for the closest match
+const ATTEMPTS: usize = 20;
+
+/// How many generated levels are remembered
+const MAX_GENERATED: usize = 8;
+
+/// The levels generated most recently, so restarting or going back to one doesn't search for it again.
+/// A level which another thread is still generating is waited for rather than generated twice
+static GENERATED: OnceCell<Mutex<VecDeque<GeneratedLevel>>> = OnceCell::new();
+
+type GeneratedLevel = (LevelParameters, u64, Arc<OnceCell<GameLevel>>);
+
+/// Controls what sort of level is generated
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LevelParameters {
+    /// The number of objectives
+    pub objectives: usize,
+    /// The chords which objectives can require
+    pub chords: Vec<Chord>,
+    /// The number of extra notes which are not needed for any objective
+    pub decoys: usize,
+    /// How many notes each objective should have in common with the previous one
+    pub shared_notes: usize,
+    /// The ideal number of moves in the shortest solution
+    pub target_moves: usize,
+}
+
+impl LevelParameters {
+    /// Parameters which get gradually harder as the difficulty increases from zero
+    pub fn for_difficulty(difficulty: usize) -> Self {
+        let objectives = (2 + difficulty / 10).min(4);
+
+        let chords = if difficulty < 3 {
+            vec![Chord::Major, Chord::Minor]
+        } else if difficulty < 6 {
+            vec![
+                Chord::Major,
+                Chord::Minor,
+                Chord::Diminished,
+                Chord::Augmented,
+                Chord::Suspended2,
+                Chord::Suspended4,
+            ]
+        } else if difficulty < 10 {
+            vec![
+                Chord::Major,
+                Chord::Minor,
+                Chord::Diminished,
+                Chord::Augmented,
+                Chord::Suspended2,
+                Chord::Suspended4,
+                Chord::Dominant7,
+                Chord::Major7,
+                Chord::Minor7,
+            ]
+        } else if difficulty < 15 {
+            Chord::all()
+                .values()
+                .filter(|c| c.intervals().len() <= 4)
+                .cloned()
+                .collect_vec()
+        } else {
+            Chord::all().values().cloned().collect_vec()
+        };
+
+        let moves_to_build = chords.iter().map(|c| moves_to_combine(c.intervals().len()));
+        let smallest = moves_to_build.clone().min().unwrap_or(2);
+        let largest = moves_to_build.max().unwrap_or(2);
+        let target_moves = (objectives * smallest + difficulty / 4).min(objectives * largest);
+
+        Self {
+            objectives,
+            chords,
+            decoys: (difficulty / 3).min(4),
+            shared_notes: (difficulty / 5).min(2),
+            target_moves,
+        }
+    }
+
+    /// Generate a solvable level with these parameters. The same parameters and seed always give the same level
+    pub fn generate(&self, seed: u64) -> GameLevel {
+        let level = {
+            let mut generated = GENERATED.get_or_init(Default::default).lock().unwrap();
+            match generated.iter().find(|(p, s, _)| p == self && *s == seed) {
+                Some((_, _, level)) => level.clone(),
+                None => {
+                    if generated.len() >= MAX_GENERATED {
+                        generated.pop_front();
+                    }
+                    let level = Arc::<OnceCell<GameLevel>>::default();
+                    generated.push_back((self.clone(), seed, level.clone()));
+                    level
+                }
+            }
+        };
+
+        level.get_or_init(|| self.search(seed)).clone()
+    }
+
+    /// Try random levels until one needs the target number of moves
+    fn search(&self, seed: u64) -> GameLevel {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut best: Option<(usize, GameLevel)> = None;
+
+        for _ in 0..ATTEMPTS {
+            let level = self.random_level(&mut rng);
+
+            let moves = match solve(&level.objectives, &level.notes) {
+                Ok(solution) => solution.moves.len(),
+                Err(_) => continue,
+            };
+
+            let distance = moves.abs_diff(self.target_moves);
+            if distance == 0 {
+                return level;
+            }
+            if best.as_ref().map(|b| distance < b.0).unwrap_or(true) {
+                best = Some((distance, level));
+            }
+        }
+
+        best.map(|b| b.1).unwrap_or_else(|| GameLevel {
+            header: "".to_string(),
+            name: "Back to Basics".to_string(),
+            objectives: vec![Chord::Major.into()],
+            notes: vec![Note::C.into(), Note::E.into(), Note::G.into()],
+            chords: vec![],
+            key: None,
+            tuning: None,
+            instrument: None,
+        })
+    }
+
+    fn random_level(&self, rng: &mut StdRng) -> GameLevel {
+        let mut objectives = Vec::<ChordFilter>::new();
+        let mut notes = Vec::<Note>::new();
+        let mut previous_notes = Vec::<Note>::new();
+
+        for i in 0..self.objectives {
+            let remaining_objectives = self.objectives - i;
+            let moves_so_far: usize = objectives
+                .iter()
+                .filter_map(|o| o.chord)
+                .map(|c| moves_to_combine(c.intervals().len()))
+                .sum();
+            let moves_each = self.target_moves.saturating_sub(moves_so_far) / remaining_objectives;
+
+            let (chord, root) = (0..ATTEMPTS)
+                .map(|_| {
+                    let chord = *self.chords.choose(rng).unwrap_or(&Chord::Major);
+                    let root = Note(rng.gen_range(0..12));
+                    (chord, root)
+                })
+                .min_by_key(|(chord, root)| {
+                    let shared = chord
+                        .get_notes(*root)
+                        .iter()
+                        .filter(|n| previous_notes.contains(n))
+                        .count();
+                    let moves = moves_to_combine(chord.intervals().len());
+
+                    let shared_distance = if i == 0 {
+                        0
+                    } else {
+                        shared.abs_diff(self.shared_notes)
+                    };
+
+                    shared_distance + moves.abs_diff(moves_each)
+                })
+                .unwrap();
+
+            previous_notes = chord.get_notes(root);
+            notes.extend(previous_notes.iter().cloned());
+            objectives.push(chord.into());
+        }
+
+        for _ in 0..self.decoys {
+            notes.push(Note(rng.gen_range(0..12)));
+        }
+        notes.shuffle(rng);
+
+        let first_chord = objectives.iter().find_map(|o| o.chord);
+        let name = first_chord
+            .map(|c| *puns(c).choose(rng).unwrap())
+            .unwrap_or("Free Jazz")
+            .to_string();
+
+        GameLevel {
+            header: "".to_string(),
+            name,
+            objectives,
+            notes: notes.into_iter().map(Pitch::from).collect(),
+            chords: vec![],
+            key: None,
+            tuning: None,
+            instrument: None,
+        }
+    }
+}
+
+/// Roman numeral level headers, lower case if the first objective is a minor-ish chord
+pub fn level_header(i: usize, first_objective: Option<Chord>) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+
+    let mut remaining = i;
+    let mut header = String::new();
+    for (value, numeral) in NUMERALS {
+        while remaining >= value {
+            header.push_str(numeral);
+            remaining -= value;
+        }
+    }
+    header.push('.');
+
+    let is_minor = first_objective
+        .map(|c| c.intervals().contains(&3) && !c.intervals().contains(&4))
+        .unwrap_or(false);
+
+    if is_minor {
+        header.to_lowercase()
+    } else {
+        header
+    }
+}
+
+fn puns(chord: Chord) -> &'static [&'static str] {
+    match chord {
+        Chord::Major => &[
+            "Major Incident",
+            "Major Breakthrough",
+            "It's a Major Undertaking",
+        ],
+        Chord::Minor => &["Minor Inconvenience", "Minor Details", "Mine Shaft Gap"],
+        Chord::Diminished => &["Diminished Returns", "Diminished Responsibility"],
+        Chord::Augmented => &["Augmented Reality", "Aug-mental Gymnastics"],
+        Chord::Suspended2 | Chord::Suspended4 => &[
+            "Suspended Animation",
+            "Suspended Sentence",
+            "The Suspense is Killing Me",
+        ],
+        Chord::Dominant7 => &["Seventh Heaven", "Dominant Strategy"],
+        Chord::Major7 => &["Seventh Heaven", "Major Seventh Son"],
+        Chord::Minor7 => &["Seven Minor Wonders", "Minor Seventh Son"],
+        Chord::MinorMajor7 => &["Identity Crisis", "Major Minor Drama"],
+        Chord::HalfDiminished => &["Half Measures", "Half Baked"],
+        Chord::Diminished7 => &["Diminishing Sevens", "Symmetry Breaking"],
+        Chord::Augmented7 | Chord::AugmentedMaj7 => {
+            &["Augmented Reality Check", "Aug-ment Your Expectations"]
+        }
+        Chord::Power => &["Power Trip", "Power Cut", "Fifth Element"],
+        Chord::Dominant7Sus4 => &["Suspended Sentence", "Seventh Heaven Can Wait"],
+        Chord::Major6 | Chord::Minor6 => &["Sixth Sense", "Six of One"],
+        Chord::Add9 | Chord::MinorAdd9 => &["Added Extras", "Cloud Nine"],
+        Chord::Dominant9 | Chord::Major9 | Chord::Minor9 => {
+            &["Cloud Nine", "Dressed to the Nines", "Nine Lives"]
+        }
+        Chord::Dominant7Flat9 | Chord::Dominant7Sharp9 => {
+            &["Purple Haze", "Sharp Practice", "Flat Out"]
+        }
+        Chord::Dominant11 => &["Eleventh Hour", "Up to Eleven"],
+        Chord::Dominant13 => &["Unlucky Thirteen", "Baker's Dozen"],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::solve;
+
+    #[test]
+    fn difficulty_ramps_up_smoothly() {
+        for difficulty in 0..40 {
+            let easier = LevelParameters::for_difficulty(difficulty);
+            let harder = LevelParameters::for_difficulty(difficulty + 1);
+
+            assert!((easier.objectives..=easier.objectives + 1).contains(&harder.objectives));
+            assert!((easier.decoys..=easier.decoys + 1).contains(&harder.decoys));
+            assert!((easier.shared_notes..=easier.shared_notes + 1).contains(&harder.shared_notes));
+            assert!((easier.target_moves..=easier.target_moves + 2).contains(&harder.target_moves));
+            assert!(
+                easier.chords.iter().all(|c| harder.chords.contains(c)),
+                "difficulty {difficulty} loses chords"
+            );
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_level() {
+        for difficulty in [0, 7, 25] {
+            let parameters = LevelParameters::for_difficulty(difficulty);
+            for seed in 0..5 {
+                assert_eq!(
+                    format!("{:?}", parameters.search(seed)),
+                    format!("{:?}", parameters.search(seed))
+                );
+            }
+
+            let levels = (0..5)
+                .map(|seed| format!("{:?}", parameters.search(seed)))
+                .unique()
+                .count();
+            assert!(levels > 1, "difficulty {difficulty} only makes one level");
+        }
+    }
+
+    #[test]
+    fn random_levels_are_solvable() {
+        for difficulty in 0..30 {
+            let parameters = LevelParameters::for_difficulty(difficulty);
+            let mut rng = StdRng::seed_from_u64(difficulty as u64);
+            for _ in 0..ATTEMPTS {
+                let level = parameters.random_level(&mut rng);
+                assert_eq!(level.objectives.len(), parameters.objectives);
+                assert!(
+                    solve(&level.objectives, &level.notes).is_ok(),
+                    "difficulty {difficulty}: {level:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generated_levels_need_the_target_moves() {
+        for difficulty in 0..30 {
+            let parameters = LevelParameters::for_difficulty(difficulty);
+            let level = parameters.generate(difficulty as u64);
+            let solution = solve(&level.objectives, &level.notes).unwrap();
+            assert_eq!(
+                solution.moves.len(),
+                parameters.target_moves,
+                "difficulty {difficulty}"
+            );
+        }
+    }
+}
@@ -5,7 +5,9 @@ use crate::*;
 pub struct HoverPlugin;
 impl Plugin for HoverPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(detect_hover.label("detect_hover"));
+        app.add_system_set(
+            SystemSet::on_update(Screen::Playing).with_system(detect_hover.label("detect_hover")),
+        );
     }
 }
 
@@ -8,7 +8,10 @@ use crate::*;
 pub struct InputPlugin;
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(mousebutton_listener.label("mousebutton_listener"));
+        app.add_system_set(
+            SystemSet::on_update(Screen::Playing)
+                .with_system(mousebutton_listener.label("mousebutton_listener")),
+        );
     }
 }
 
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy_fundsp::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+pub struct InstrumentPlugin;
+impl Plugin for InstrumentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(handle_instrument_input);
+    }
+}
+
+/// The sound the notes are played with
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum Instrument {
+    #[default]
+    SoftPad,
+    ElectricPiano,
+    Organ,
+    PluckedString,
+    Choir,
+}
+
+impl Instrument {
+    pub const ALL: [Instrument; 5] = [
+        Instrument::SoftPad,
+        Instrument::ElectricPiano,
+        Instrument::Organ,
+        Instrument::PluckedString,
+        Instrument::Choir,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instrument::SoftPad => "Soft pad",
+            Instrument::ElectricPiano => "Electric piano",
+            Instrument::Organ => "Organ",
+            Instrument::PluckedString => "Plucked string",
+            Instrument::Choir => "Choir",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|i| i == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// A stereo graph playing this instrument at the frequency.
+    /// Envelopes start when the graph is reset, which happens each time the note starts sounding
+    pub fn graph(&self, hz: f32) -> Box<dyn AudioUnit32> {
+        match self {
+            Instrument::SoftPad => Box::new(
+                (triangle_hz(hz) + sine_hz(hz * 2.)) >> lowpole_hz(100.0) >> (split::<U2>() * 0.2),
+            ),
+            Instrument::ElectricPiano => Box::new(
+                (sine_hz(hz) + sine_hz(hz * 2.) * 0.3 + sine_hz(hz * 7.) * 0.05)
+                    * envelope(|t| 0.2 + 0.8 * (-t * 1.5).exp())
+                    >> (split::<U2>() * 0.2),
+            ),
+            Instrument::Organ => Box::new(
+                (sine_hz(hz)
+                    + sine_hz(hz * 2.) * 0.5
+                    + sine_hz(hz * 3.) * 0.3
+                    + sine_hz(hz * 4.) * 0.2)
+                    >> (split::<U2>() * 0.1),
+            ),
+            Instrument::PluckedString => Box::new(
+                (saw_hz(hz) >> lowpole_hz(hz * 3.)) * envelope(|t| (-t * 3.).exp())
+                    >> (split::<U2>() * 0.3),
+            ),
+            Instrument::Choir => {
+                //Three slightly detuned voices with a little vibrato, softened like a vowel
+                let voice =
+                    |detune: f32| (dc(hz * detune) + sine_hz(5. * detune) * (hz * 0.006)) >> saw();
+                Box::new(
+                    (voice(1.) + voice(1.004) + voice(0.996))
+                        >> lowpass_hz(900., 1.0)
+                        >> (split::<U2>() * 0.1),
+                )
+            }
+        }
+    }
+}
+
+fn handle_instrument_input(keys: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keys.just_pressed(KeyCode::I) {
+        settings.instrument = settings.instrument.next();
+        info!("Instrument: {}", settings.instrument.name());
+    }
+}
@@ -4,26 +4,36 @@ use bevy::prelude::*;
 use bevy_tweening::lens::*;
 use bevy_tweening::*;
 use itertools::Itertools;
-use rand::Rng;
+use serde::{Deserialize, Deserializer};
 
-use rand::SeedableRng;
-use strum::EnumCount;
-
-use crate::chord::Chord;
+use crate::campaign::*;
+use crate::chord::{ChordFilter, ChordPreference, ChordSymbol};
 use crate::cluster::*;
 use crate::objective::*;
+use crate::solver::{self, Solution, SolverError};
+use crate::spelling::SpelledNote;
 use crate::*;
 
 pub struct LevelPlugin;
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CurrentLevel>()
+            .init_resource::<GameMode>()
+            .init_resource::<LevelStats>()
+            .init_resource::<ChordPreference>()
+            .init_resource::<LevelKey>()
+            .init_resource::<LevelTuning>()
+            .init_resource::<LevelInstrument>()
             .add_system_to_stage(
                 CoreStage::PreUpdate,
-                check_for_completion, //.after("update_met_objectives"),
+                check_for_completion.label("check_for_completion"), //.after("update_met_objectives"),
             )
             .add_startup_system(setup_level_text)
-            .add_startup_system_to_stage(StartupStage::PostStartup, start_next_level);
+            .add_system(reload_level)
+            .add_system(go_to_level)
+            .add_system_set(SystemSet::on_update(Screen::Playing).with_system(handle_restart_input))
+            .add_system(restart_level)
+            .add_system(reset_level_stats);
     }
 }
 
@@ -41,6 +51,16 @@ fn setup_level_text(mut commands: Commands, asset_server: Res<AssetServer>) {
             ..Default::default()
         })
         .with_children(|f| {
+            f.spawn_bundle(
+                TextBundle::from_sections([TextSection::from_style(TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 14.0,
+                    color: ERROR_TEXT_COLOR,
+                })]) // Set the alignment of the Text
+                .with_text_alignment(TextAlignment::CENTER),
+            )
+            .insert(LevelErrorText);
+
             f.spawn_bundle(
                 TextBundle::from_sections([TextSection::from_style(TextStyle {
                     font: asset_server.load("fonts/FiraMono-Medium.ttf"),
@@ -62,39 +82,193 @@ fn setup_level_text(mut commands: Commands, asset_server: Res<AssetServer>) {
             .insert(LevelText { is_header: true });
         });
 }
+
 fn check_for_completion(
     mut commands: Commands,
     added_completions: Query<Added<CompletingObjective>>,
-    objectives: Query<(Entity, &Objective)>,
-    orbs: Query<(Entity, &Orb)>,
-    current_level: ResMut<CurrentLevel>,
-    level_text: Query<(Entity, &LevelText, &mut Text)>,
+    completions: Query<(&Orb, &CompletingObjective)>,
+    objectives: Query<(Entity, &Objective, &Transform)>,
+    level_entities: Query<Entity, Or<(With<Objective>, With<Orb>)>>,
+    mut current_level: ResMut<CurrentLevel>,
+    game_mode: Res<GameMode>,
+    stats: Res<LevelStats>,
+    time: Res<Time>,
+    mut level_text: Query<(Entity, &LevelText, &mut Text)>,
+    campaign_handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+    mut ew: EventWriter<LevelCompletedEvent>,
 ) {
     if !added_completions.is_empty() && objectives.iter().all(|o| o.1.is_complete) {
-        for (e, _) in objectives.iter() {
-            commands.entity(e).despawn_recursive();
-        }
-        for (e, _) in orbs.iter() {
-            commands.entity(e).despawn_recursive();
+        let chords = objectives
+            .iter()
+            .sorted_by(|a, b| a.2.translation.x.total_cmp(&b.2.translation.x))
+            .filter_map(|(entity, _, _)| {
+                completions
+                    .iter()
+                    .find(|(_, c)| c.objective == entity)
+                    .map(|(orb, _)| orb.cluster.clone())
+            })
+            .collect();
+
+        clear_level(&mut commands, &level_entities);
+
+        ew.send(LevelCompletedEvent {
+            game_mode: *game_mode,
+            level: current_level.0,
+            moves: stats.moves,
+            seconds: time.seconds_since_startup() - stats.start_time,
+            chords,
+        });
+
+        if *game_mode == GameMode::Campaign {
+            if let Some(campaign) = campaigns.get(&campaign_handle.0) {
+                current_level.0 += 1;
+                start_level(
+                    &mut commands,
+                    &campaign.get_level(current_level.0),
+                    &mut level_text,
+                );
+            }
         }
+    }
+}
 
-        start_next_level(commands, current_level, level_text)
+fn reload_level(
+    mut commands: Commands,
+    mut er: EventReader<AssetEvent<Campaign>>,
+    level_entities: Query<Entity, Or<(With<Objective>, With<Orb>)>>,
+    mut current_level: ResMut<CurrentLevel>,
+    game_mode: Res<GameMode>,
+    mut level_text: Query<(Entity, &LevelText, &mut Text)>,
+    mut error_text: Query<&mut Text, (With<LevelErrorText>, Without<LevelText>)>,
+    campaigns: Res<Assets<Campaign>>,
+) {
+    for ev in er.iter() {
+        let handle = match ev {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+
+        if let Some(campaign) = campaigns.get(handle) {
+            for mut text in error_text.iter_mut() {
+                text.sections[0].value = campaign.error.clone().unwrap_or_default();
+            }
+
+            if campaign.error.is_none() && *game_mode == GameMode::Campaign {
+                clear_level(&mut commands, &level_entities);
+
+                current_level.0 = current_level.0.max(1);
+                start_level(
+                    &mut commands,
+                    &campaign.get_level(current_level.0),
+                    &mut level_text,
+                );
+            }
+        }
     }
 }
 
-fn start_next_level(
+fn go_to_level(
     mut commands: Commands,
+    mut er: EventReader<GoToLevelEvent>,
+    level_entities: Query<Entity, Or<(With<Objective>, With<Orb>)>>,
     mut current_level: ResMut<CurrentLevel>,
+    mut game_mode: ResMut<GameMode>,
     mut level_text: Query<(Entity, &LevelText, &mut Text)>,
+    campaign_handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
 ) {
-    current_level.0 += 1;
-    let level = GameLevel::get_level(current_level.0);
+    if let Some(ev) = er.iter().last() {
+        if let Some(campaign) = campaigns.get(&campaign_handle.0) {
+            clear_level(&mut commands, &level_entities);
+
+            *game_mode = GameMode::Campaign;
+            current_level.0 = ev.0.max(1);
+            start_level(
+                &mut commands,
+                &campaign.get_level(current_level.0),
+                &mut level_text,
+            );
+        }
+    }
+}
+
+fn handle_restart_input(
+    keys: Res<Input<KeyCode>>,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<RestartButton>)>,
+    mut ew: EventWriter<NewGameEvent>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if (keys.just_pressed(KeyCode::R) && !ctrl)
+        || buttons.iter().any(|i| *i == Interaction::Clicked)
+    {
+        ew.send(NewGameEvent);
+    }
+}
 
+fn restart_level(
+    mut commands: Commands,
+    mut er: EventReader<NewGameEvent>,
+    level_entities: Query<Entity, Or<(With<Objective>, With<Orb>)>>,
+    current_level: Res<CurrentLevel>,
+    game_mode: Res<GameMode>,
+    mut level_text: Query<(Entity, &LevelText, &mut Text)>,
+    campaign_handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+) {
+    if er.iter().count() == 0 {
+        return;
+    }
+
+    let level = match *game_mode {
+        GameMode::Campaign => campaigns
+            .get(&campaign_handle.0)
+            .map(|c| c.get_level(current_level.0.max(1))),
+        GameMode::Daily { day } => Some(daily_level(day)),
+    };
+
+    if let Some(level) = level {
+        clear_level(&mut commands, &level_entities);
+        start_level(&mut commands, &level, &mut level_text);
+    }
+}
+
+fn reset_level_stats(
+    added_objectives: Query<Added<Objective>>,
+    time: Res<Time>,
+    mut stats: ResMut<LevelStats>,
+) {
+    if !added_objectives.is_empty() {
+        *stats = LevelStats {
+            moves: 0,
+            start_time: time.seconds_since_startup(),
+        };
+    }
+}
+
+/// Despawn all the objectives and orbs of the current level
+pub fn clear_level(
+    commands: &mut Commands,
+    level_entities: &Query<Entity, Or<(With<Objective>, With<Orb>)>>,
+) {
+    for e in level_entities.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+/// Show a message in the level text, which slowly fades away
+pub fn set_level_text(
+    commands: &mut Commands,
+    level_text: &mut Query<(Entity, &LevelText, &mut Text)>,
+    header: &str,
+    name: &str,
+) {
     for (entity, lt, mut text) in level_text.iter_mut() {
         let new_text = if lt.is_header {
-            format!("{: ^60}", level.header)
+            format!("{: ^60}", header)
         } else {
-            format!("{: ^36}", level.name)
+            format!("{: ^36}", name)
         };
 
         text.sections[0].value = new_text;
@@ -109,20 +283,32 @@ fn start_next_level(
             },
         )));
     }
+}
+
+pub fn start_level(
+    commands: &mut Commands,
+    level: &GameLevel,
+    level_text: &mut Query<(Entity, &LevelText, &mut Text)>,
+) {
+    set_level_text(commands, level_text, &level.header, &level.name);
+    commands.insert_resource(level.chord_preference());
+    commands.insert_resource(LevelKey(level.key));
+    commands.insert_resource(LevelTuning(level.tuning.clone()));
+    commands.insert_resource(LevelInstrument(level.instrument));
 
     for (i, objective) in level.objectives.iter().enumerate() {
-        create_objective(&mut commands, i, level.objectives.len(), *objective);
+        create_objective(commands, i, level.objectives.len(), *objective);
     }
 
-    for n in level.notes {
+    for n in level.pitches() {
         let rangex = -100f32..100f32;
         let rangey = -100f32..100f32;
 
         create_orb_near(
-            &mut commands,
+            commands,
             SHAPE_SIZE,
             Cluster {
-                notes: smallvec::smallvec![n],
+                pitches: smallvec::smallvec![n],
             },
             rangex,
             rangey,
@@ -136,134 +322,157 @@ pub struct LevelText {
     is_header: bool,
 }
 
+/// Shows why the level file could not be loaded
+#[derive(Component)]
+pub struct LevelErrorText;
+
 #[derive(Default)]
 pub struct CurrentLevel(pub usize);
 
+/// The key of the level being played, which notes are spelled to suit
+#[derive(Default)]
+pub struct LevelKey(pub Option<SpelledNote>);
+
+/// The tuning system of the level being played, if it has its own
+#[derive(Default)]
+pub struct LevelTuning(pub Option<TuningSystem>);
+
+/// The instrument of the level being played, if it has its own
+#[derive(Default)]
+pub struct LevelInstrument(pub Option<Instrument>);
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    #[default]
+    Campaign,
+    /// The daily challenge for this many days after 1970-01-01
+    Daily { day: u32 },
+}
+
+/// How the player is doing on the current level
+#[derive(Default)]
+pub struct LevelStats {
+    pub moves: usize,
+    pub start_time: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct GameLevel {
-    pub header: &'static str,
-    pub name: &'static str,
-    pub objectives: Vec<Option<Chord>>, //change this
-    pub notes: Vec<Note>,
+    pub header: String,
+    pub name: String,
+    pub objectives: Vec<ChordFilter>,
+    /// A starting orb for each of these pitches
+    #[serde(deserialize_with = "deserialize_notes")]
+    pub notes: Vec<Pitch>,
+    /// A starting orb for each note of these chords
+    #[serde(default)]
+    pub chords: Vec<ChordSymbol>,
+    /// Ambiguous chords are named, and notes are spelled, to suit this major key
+    #[serde(default)]
+    pub key: Option<SpelledNote>,
+    /// Overrides the player's tuning system while the level is played
+    #[serde(default)]
+    pub tuning: Option<TuningSystem>,
+    /// Overrides the player's instrument while the level is played
+    #[serde(default)]
+    pub instrument: Option<Instrument>,
 }
 
 impl GameLevel {
-    fn random_level(i: usize) -> GameLevel {
-        let mut rng: rand::rngs::StdRng = SeedableRng::seed_from_u64(i as u64);
-
-        let mut objectives = Vec::<Option<Chord>>::new();
-        let mut notes = Vec::<Note>::new();
-
-        for _ in 0..2 {
-            let chord_i = rng.gen_range(0..Chord::COUNT);
-            let root_i = rng.gen_range(0..12) as u8;
-
-            let chord = Chord::from_repr(chord_i).unwrap();
-            let root = Note(root_i);
-            objectives.push(Some(chord));
-            let chord_notes = chord.get_notes(root);
-            for n in chord_notes {
-                notes.push(n);
-            }
-        }
+    pub fn chord_preference(&self) -> ChordPreference {
+        self.key
+            .map(|k| ChordPreference::Key(k.note()))
+            .unwrap_or(ChordPreference::Simplest)
+    }
 
-        let header = "";
+    /// The pitch of every starting orb, from both the notes and the chords
+    pub fn pitches(&self) -> Vec<Pitch> {
+        let chord_pitches = self.chords.iter().flat_map(|c| c.pitches());
+        self.notes.iter().copied().chain(chord_pitches).collect()
+    }
 
-        let name = match i % 5 {
-            0 => "Levels are Random now",
-            1 => "You can stop!",
-            2 => "No really, you can!",
-            3 => "Hey, you're getting good at this!",
-            4 => "Is it sight reading, or right seeding?",
+    /// Find the shortest way to complete every objective
+    pub fn solve(&self) -> Result<Solution, SolverError> {
+        solver::solve(&self.objectives, &self.pitches())
+    }
+}
 
-            _ => unimplemented!(),
-        };
+/// Notes are note names with an optional octave.
+/// Chord symbols belong in `chords`, so that e.g. "C7" is always the pitch and never the chord
+fn deserialize_notes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Pitch>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|name| {
+            name.parse::<Pitch>().map_err(|err| {
+                if name.parse::<ChordSymbol>().is_ok() {
+                    serde::de::Error::custom(format!(
+                        "'{name}' is a chord symbol, which goes in `chords` rather than `notes`"
+                    ))
+                } else {
+                    serde::de::Error::custom(err)
+                }
+            })
+        })
+        .collect()
+}
 
-        GameLevel {
-            name,
-            header,
-            objectives,
-            notes,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_level(notes: &str, chords: &str) -> Result<GameLevel, ron::Error> {
+        ron::from_str(&format!(
+            r#"(header: "", name: "", objectives: [], notes: {notes}, chords: {chords})"#
+        ))
     }
 
-    pub fn get_level(i: usize) -> GameLevel {
-        match i {
-            1 => GameLevel {
-                name: "Harmonious Materials",
-                header: "I.",
-                objectives: vec![Some(Chord::Major)],
-                notes: vec![Note::C, Note::E, Note::G],
-            },
-            2 => GameLevel {
-                header: "ii.",
-                name: "Piano Down a Mine Shaft",
-                objectives: vec![Some(Chord::Minor)],
-                notes: vec![Note::AB, Note::B, Note::C, Note::EB],
-            },
-            3 => GameLevel {
-                header: "iii.",
-                name: "Interval Training",
-                objectives: vec![Some(Chord::Major), Some(Chord::Major)],
-                notes: vec![Note::C, Note::C, Note::E, Note::G, Note::F, Note::A],
-            },
-            4 => GameLevel {
-                header: "IV.",
-                name: "Invariant Ringlet",
-                objectives: vec![Some(Chord::Suspended4), Some(Chord::Minor)],
-                notes: vec![Note::C, Note::C, Note::E, Note::G, Note::F, Note::A],
-            },
-            5 => GameLevel {
-                header: "V.",
-                name: "Dissonant Constonants",
-                objectives: vec![Some(Chord::Diminished), Some(Chord::Diminished)],
-                notes: vec![Note::D, Note::B, Note::F, Note::DB, Note::G, Note::E],
-            },
-            6 => GameLevel {
-                header: "vi.",
-                name: "Auganized Chaos",
-                objectives: vec![Some(Chord::Augmented), Some(Chord::Augmented)],
-                notes: vec![Note::A, Note::B, Note::DB, Note::EB, Note::F, Note::G],
-            },
-            7 => GameLevel {
-                header: "VII.",
-                name: "Try Tone Substitution",
-                objectives: vec![Some(Chord::Dominant7), Some(Chord::Dominant7)],
-                notes: vec![
-                    Note::A,
-                    Note::DB,
-                    Note::G,
-                    Note::E,
-                    Note::EB,
-                    Note::DB,
-                    Note::G,
-                    Note::BB,
-                ],
-            },
+    fn pitches(names: &[&str]) -> Vec<Pitch> {
+        names.iter().map(|p| p.parse().unwrap()).collect()
+    }
 
-            8 => GameLevel {
-                header: "VIII.",
-                name: "I'm too young to Diatonic",
-                objectives: vec![Some(Chord::Major7), Some(Chord::Minor7)],
-                notes: vec![
-                    Note::C,
-                    Note::C,
-                    Note::D,
-                    Note::E,
-                    Note::F,
-                    Note::G,
-                    Note::A,
-                    Note::B,
-                ],
-            },
+    #[test]
+    fn notes_which_look_like_chords_are_pitches() {
+        let level = parse_level(r#"["C7", "A6", "G9", "C5", "Eb3"]"#, "[]").unwrap();
+        assert_eq!(level.notes, pitches(&["C7", "A6", "G9", "C5", "Eb3"]));
+    }
 
-            9 => GameLevel {
-                header: "IX.",
-                name: "Chromatic Tac Toe",
-                objectives: vec![None, None, None],
-                notes: (0..12).map(Note).collect_vec(),
-            },
+    #[test]
+    fn chords_stand_for_their_notes_with_the_bass_below() {
+        let level = parse_level(r#"["D5"]"#, r#"["C7", "C/E"]"#).unwrap();
+        assert_eq!(
+            level.pitches(),
+            pitches(&["D5", "C4", "E4", "G4", "Bb4", "E3", "C4", "G4"])
+        );
+    }
+
+    #[test]
+    fn chords_are_stacked_up_from_their_root() {
+        let level = parse_level("[]", r#"["Am7", "F#m", "G7/B", "Bbmaj7/D"]"#).unwrap();
+        assert_eq!(
+            level.pitches(),
+            pitches(&[
+                "A4", "C5", "E5", "G5", "F#4", "A4", "C#5", "B3", "G4", "D5", "F5", "D3", "Bb4",
+                "F5", "A5"
+            ])
+        );
+    }
 
-            _ => Self::random_level(i),
+    #[test]
+    fn the_lowest_pitch_of_a_chord_is_its_bass() {
+        for symbol in ["Am7", "F#m7", "G", "Gsus4", "Ab6", "C/E", "G7/B", "F#m/C#"] {
+            let symbol = symbol.parse::<ChordSymbol>().unwrap();
+            let lowest = symbol.pitches().into_iter().min().unwrap();
+            assert_eq!(
+                lowest.note(),
+                symbol.bass.unwrap_or(symbol.root),
+                "{symbol}"
+            );
         }
     }
+
+    #[test]
+    fn chord_symbols_in_notes_are_an_error() {
+        let err = parse_level(r#"["Am7"]"#, "[]").unwrap_err();
+        assert!(err.to_string().contains("chords"), "{err}");
+    }
 }
@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+pub struct LevelSelectPlugin;
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(Screen::Playing)
+            .add_system(open_level_select)
+            .add_system_set(
+                SystemSet::on_enter(Screen::LevelSelect).with_system(spawn_level_select),
+            )
+            .add_system_set(
+                SystemSet::on_update(Screen::LevelSelect).with_system(handle_level_select_buttons),
+            )
+            .add_system_set(
+                SystemSet::on_exit(Screen::LevelSelect).with_system(despawn_level_select),
+            );
+    }
+}
+
+/// Which screen is showing. The playing field only takes input when it is showing
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Screen {
+    Playing,
+    LevelSelect,
+}
+
+#[derive(Component)]
+pub struct LevelSelectRoot;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LevelSelectButton {
+    Level(usize),
+    Back,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LevelStatus {
+    Locked,
+    Unlocked,
+    Completed,
+}
+
+impl LevelStatus {
+    pub fn of(level: usize, save_data: &SaveData) -> Self {
+        if save_data.level_records.contains_key(&level) {
+            LevelStatus::Completed
+        } else if level <= save_data.highest_level.max(1) {
+            LevelStatus::Unlocked
+        } else {
+            LevelStatus::Locked
+        }
+    }
+}
+
+fn open_level_select(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut screen: ResMut<State<Screen>>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction == Interaction::Clicked
+            && *button == MenuButton::Levels
+            && screen.current() == &Screen::Playing
+        {
+            screen.set(Screen::LevelSelect).unwrap();
+        }
+    }
+}
+
+fn spawn_level_select(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    save_data: Res<SaveData>,
+    campaign_handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+) {
+    let levels = campaigns
+        .get(&campaign_handle.0)
+        .map(|c| c.levels.clone())
+        .unwrap_or_default();
+
+    let button_text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 20.0,
+        color: SMALL_TEXT_COLOR,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            color: CLEAR_COLOR.into(),
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::FlexStart,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(LevelSelectRoot)
+        .with_children(|f| {
+            f.spawn_bundle(TextBundle::from_section(
+                "Levels",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: BIG_TEXT_COLOR,
+                },
+            ));
+
+            for (index, level) in levels.iter().enumerate() {
+                let level_number = index + 1;
+                let status = LevelStatus::of(level_number, &save_data);
+
+                let description = match status {
+                    LevelStatus::Locked => format!("{} locked", level.header),
+                    LevelStatus::Unlocked => format!("{} {}", level.header, level.name),
+                    LevelStatus::Completed => {
+                        let moves = save_data.level_records[&level_number].moves;
+                        format!("{} {} ({moves} moves)", level.header, level.name)
+                    }
+                };
+
+                let color = match status {
+                    LevelStatus::Locked => LOCKED_BUTTON_COLOR,
+                    LevelStatus::Unlocked => MENU_BUTTON_COLOR,
+                    LevelStatus::Completed => COMPLETED_BUTTON_COLOR,
+                };
+
+                let mut button = f.spawn_bundle(ButtonBundle {
+                    color: color.into(),
+                    style: level_select_button_style(),
+                    ..Default::default()
+                });
+                button.with_children(|b| {
+                    b.spawn_bundle(TextBundle::from_section(
+                        description,
+                        button_text_style.clone(),
+                    ));
+                });
+
+                if status != LevelStatus::Locked {
+                    button.insert(LevelSelectButton::Level(level_number));
+                }
+            }
+
+            f.spawn_bundle(ButtonBundle {
+                color: MENU_BUTTON_COLOR.into(),
+                style: level_select_button_style(),
+                ..Default::default()
+            })
+            .insert(LevelSelectButton::Back)
+            .with_children(|b| {
+                b.spawn_bundle(TextBundle::from_section("Back", button_text_style.clone()));
+            });
+        });
+}
+
+fn level_select_button_style() -> Style {
+    Style {
+        size: Size::new(Val::Percent(90.), Val::Auto),
+        margin: UiRect::all(Val::Px(3.)),
+        padding: UiRect::all(Val::Px(6.)),
+        ..Default::default()
+    }
+}
+
+fn handle_level_select_buttons(
+    buttons: Query<(&Interaction, &LevelSelectButton), Changed<Interaction>>,
+    mut screen: ResMut<State<Screen>>,
+    mut ew: EventWriter<GoToLevelEvent>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            if let LevelSelectButton::Level(level) = button {
+                ew.send(GoToLevelEvent(*level));
+            }
+            screen.set(Screen::Playing).unwrap();
+            return;
+        }
+    }
+}
+
+fn despawn_level_select(mut commands: Commands, roots: Query<Entity, With<LevelSelectRoot>>) {
+    for e in roots.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+}
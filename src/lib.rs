@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+pub const WINDOW_WIDTH: f32 = 360f32;
+pub const WINDOW_HEIGHT: f32 = 640f32;
+pub const WALL_WIDTH: f32 = 360f32;
+
+pub mod draggable;
+
+pub use draggable::*;
+pub mod events;
+pub use events::*;
+pub mod components;
+pub use components::*;
+pub mod walls;
+pub use walls::*;
+pub mod cluster;
+
+pub mod sound;
+pub use sound::*;
+
+pub mod tuning;
+pub use tuning::*;
+
+pub mod instrument;
+pub use instrument::*;
+
+pub mod flourish;
+pub use flourish::*;
+
+pub mod scala;
+
+pub mod render;
+
+pub mod input;
+pub use input::*;
+
+pub mod orb;
+pub use orb::*;
+
+pub mod hover;
+pub use hover::*;
+
+pub mod combine;
+pub use combine::*;
+
+pub mod deconstructor;
+pub use deconstructor::*;
+
+pub mod chord;
+
+pub mod spelling;
+
+pub mod chord_text;
+pub use chord_text::*;
+
+pub mod objective;
+pub use objective::*;
+
+pub mod level;
+pub use level::*;
+
+pub mod campaign;
+pub use campaign::*;
+
+pub mod solver;
+
+pub mod generator;
+
+pub mod storage;
+
+pub mod menu;
+pub use menu::*;
+
+pub mod daily;
+pub use daily::*;
+
+pub mod save;
+pub use save::*;
+
+pub mod level_select;
+pub use level_select::*;
+
+pub mod undo;
+pub use undo::*;
+
+pub mod notes_playing;
+pub use notes_playing::*;
+
+pub mod prelude {}
+
+pub const CLEAR_COLOR: Color = Color::DARK_GRAY;
+pub const FIXED_OBJECT_STROKE: Color = Color::ANTIQUE_WHITE;
+pub const FIXED_OBJECT_FILL: Color = Color::GRAY;
+pub const COMPLETE_OBJECTIVE_FILL: Color = Color::GOLD;
+pub const EXCITED_OBJECTIVE_FILL: Color = Color::SILVER;
+pub const CHORD_COLOR: Color = Color::ANTIQUE_WHITE;
+//pub const NON_SELECTED_CHORD_COLOR :Color = Color::NONE;
+
+pub const BIG_TEXT_COLOR: Color = Color::GOLD;
+pub const SMALL_TEXT_COLOR: Color = Color::ALICE_BLUE;
+pub const ERROR_TEXT_COLOR: Color = Color::ORANGE_RED;
+
+pub const MENU_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const MENU_BUTTON_HOVER_COLOR: Color = Color::rgb(0.35, 0.35, 0.35);
+pub const LOCKED_BUTTON_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+pub const COMPLETED_BUTTON_COLOR: Color = Color::rgb(0.3, 0.25, 0.05);
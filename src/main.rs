@@ -1,4 +1,4 @@
-#![allow(dead_code)]
+use bevy::asset::AssetServerSettings;
 use bevy::DefaultPlugins;
 
 use bevy::log::*;
@@ -7,65 +7,7 @@ use bevy_prototype_lyon::prelude::*;
 use bevy_rapier2d::prelude::*;
 use bevy_tweening::TweeningPlugin;
 
-pub const WINDOW_WIDTH: f32 = 360f32;
-pub const WINDOW_HEIGHT: f32 = 640f32;
-pub const WALL_WIDTH: f32 = 360f32;
-
-mod draggable;
-
-use draggable::*;
-mod events;
-use events::*;
-mod components;
-use components::*;
-mod walls;
-use walls::*;
-mod cluster;
-
-mod sound;
-use sound::*;
-
-mod input;
-use input::*;
-
-mod orb;
-use orb::*;
-
-mod hover;
-use hover::*;
-
-mod combine;
-use combine::*;
-
-mod deconstructor;
-use deconstructor::*;
-
-mod chord;
-
-mod chord_text;
-use chord_text::*;
-
-mod objective;
-use objective::*;
-
-mod level;
-use level::*;
-
-mod notes_playing;
-use notes_playing::*;
-
-pub mod prelude {}
-
-pub const CLEAR_COLOR: Color = Color::DARK_GRAY;
-pub const FIXED_OBJECT_STROKE: Color = Color::ANTIQUE_WHITE;
-pub const FIXED_OBJECT_FILL: Color = Color::GRAY;
-pub const COMPLETE_OBJECTIVE_FILL: Color = Color::GOLD;
-pub const EXCITED_OBJECTIVE_FILL: Color = Color::SILVER;
-pub const CHORD_COLOR: Color = Color::ANTIQUE_WHITE;
-//pub const NON_SELECTED_CHORD_COLOR :Color = Color::NONE;
-
-pub const BIG_TEXT_COLOR: Color = Color::GOLD;
-pub const SMALL_TEXT_COLOR: Color = Color::ALICE_BLUE;
+use chord_fusion::*;
 
 fn main() {
     // When building for WASM, print panics to the browser console
@@ -86,6 +28,12 @@ fn main() {
             ..Default::default()
         })
         .insert_resource(ClearColor)
+        .insert_resource(AssetServerSettings {
+            // Hot-reload level files while the game is running
+            #[cfg(not(target_arch = "wasm32"))]
+            watch_for_changes: true,
+            ..Default::default()
+        })
         .add_plugins(DefaultPlugins)
         .add_plugin(TweeningPlugin)
         //.add_plugin(AudioPlugin)
@@ -98,13 +46,23 @@ fn main() {
         .add_plugin(ShapePlugin)
         .add_plugin(InputPlugin)
         .add_plugin(EventsPlugin)
-        .add_plugin(SoundPlugin)
+        .add_plugin(SoundPlugin::default())
+        .add_plugin(TuningPlugin)
+        .add_plugin(InstrumentPlugin)
+        .add_plugin(FlourishPlugin)
+        .add_plugin(scala::ScalaPlugin)
         .add_plugin(DragPlugin)
         .add_plugin(HoverPlugin)
         .add_plugin(CombinePlugin)
         .add_plugin(DeconstructPlugin)
         .add_plugin(ObjectivePlugin)
+        .add_plugin(CampaignPlugin)
         .add_plugin(LevelPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(DailyPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(LevelSelectPlugin)
+        .add_plugin(UndoPlugin)
         .add_startup_system(setup.label("main_setup"))
         //.add_startup_system_to_stage(StartupStage::PostStartup, create_initial_orbs)
         .run();
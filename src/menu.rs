@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+pub struct MenuPlugin;
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_menu)
+            .add_system(highlight_menu_buttons);
+    }
+}
+
+/// The buttons along the top of the screen
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuButton {
+    Campaign,
+    Daily,
+    Levels,
+    Undo,
+    Redo,
+    Mute,
+}
+
+impl MenuButton {
+    pub fn text(&self) -> &'static str {
+        match self {
+            MenuButton::Campaign => "Campaign",
+            MenuButton::Daily => "Daily",
+            MenuButton::Levels => "Levels",
+            MenuButton::Undo => "Undo",
+            MenuButton::Redo => "Redo",
+            MenuButton::Mute => "Mute",
+        }
+    }
+}
+
+fn setup_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            color: Color::NONE.into(),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(5.),
+                    right: Val::Px(5.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::Row,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with_children(|f| {
+            for button in [
+                MenuButton::Campaign,
+                MenuButton::Daily,
+                MenuButton::Levels,
+                MenuButton::Undo,
+                MenuButton::Redo,
+                MenuButton::Mute,
+            ] {
+                f.spawn_bundle(ButtonBundle {
+                    color: MENU_BUTTON_COLOR.into(),
+                    style: Style {
+                        margin: UiRect::all(Val::Px(2.)),
+                        padding: UiRect::all(Val::Px(4.)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(button)
+                .with_children(|b| {
+                    b.spawn_bundle(TextBundle::from_section(
+                        button.text(),
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 16.0,
+                            color: SMALL_TEXT_COLOR,
+                        },
+                    ));
+                });
+            }
+
+            f.spawn_bundle(ButtonBundle {
+                color: MENU_BUTTON_COLOR.into(),
+                style: Style {
+                    margin: UiRect::all(Val::Px(2.)),
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(RestartButton {})
+            .with_children(|b| {
+                b.spawn_bundle(TextBundle::from_section(
+                    "Restart",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 16.0,
+                        color: SMALL_TEXT_COLOR,
+                    },
+                ));
+            });
+        });
+}
+
+fn highlight_menu_buttons(
+    mut buttons: Query<
+        (&Interaction, &mut UiColor),
+        (
+            Changed<Interaction>,
+            Or<(With<MenuButton>, With<RestartButton>)>,
+        ),
+    >,
+) {
+    for (interaction, mut color) in buttons.iter_mut() {
+        *color = match interaction {
+            Interaction::Clicked | Interaction::Hovered => MENU_BUTTON_HOVER_COLOR.into(),
+            Interaction::None => MENU_BUTTON_COLOR.into(),
+        };
+    }
+}
@@ -20,13 +20,13 @@ pub fn track_notes_playing_changes(
     mut ew: EventWriter<NotesPlayingChangedEvent>,
 ) {
     if !changed_interactables.is_empty() {
-        let notes = all_orbs
+        let pitches = all_orbs
             .iter()
             .filter(|x| x.1.interacting)
-            .flat_map(|x| x.0.cluster.notes.clone())
+            .flat_map(|x| x.0.cluster.pitches.clone())
             .collect_vec();
 
         //info!("Notes playing changed");
-        ew.send(NotesPlayingChangedEvent { notes })
+        ew.send(NotesPlayingChangedEvent { pitches })
     }
 }
@@ -36,7 +36,7 @@ impl Plugin for ObjectivePlugin {
 
 #[derive(Component)]
 pub struct Objective {
-    pub filter: Option<Chord>,
+    pub filter: ChordFilter,
     pub is_complete: bool,
     pub is_hovered: bool,
 }
@@ -76,8 +76,8 @@ fn set_objective_colors(
 ) {
     if let Some(ev) = er.iter().last() {
         //something has changed. Reset chord text
-        let notes = ev
-            .notes
+        let pitches = ev
+            .pitches
             .iter()
             .sorted()
             .dedup()
@@ -85,20 +85,11 @@ fn set_objective_colors(
             .collect_vec()
             .to_smallvec();
 
-        let cluster = Cluster { notes };
-        let chord_option = cluster.get_chord();
+        let cluster = Cluster { pitches };
 
         for (objective, mut draw_mode) in objectives_query.iter_mut() {
             if !objective.is_complete {
-                let excited: bool = if let Some(chord) = chord_option {
-                    if let Some(filter) = objective.filter {
-                        filter == chord.1
-                    } else {
-                        true
-                    }
-                } else {
-                    false
-                };
+                let excited: bool = cluster.meets_filter(objective.filter);
 
                 if excited {
                     if draw_mode.ne(&incomplete_excited_objective_draw_mode()) {
@@ -117,33 +108,26 @@ fn check_for_completions(
     mut er_dragend: EventReader<DragEndWithIntersection>,
     orbs: Query<&Orb>,
     mut objectives: Query<(&mut Objective, &mut DrawMode)>,
+    mut ew: EventWriter<ObjectiveCompletedEvent>,
 ) {
     for event in er_dragend.iter() {
         if let Ok((mut objective, mut draw_mode)) = objectives.get_mut(event.target) {
             if !objective.is_complete {
                 if let Ok(orb) = orbs.get(event.dragged) {
                     //info!("Checking Orb");
-                    if let Some((_, chord)) = orb.cluster.get_chord() {
-                        //info!("Orb has chord {}", chord);
-
-                        let meets_filter = if let Some(filter) = objective.filter {
-                            //info!("Objective has filter {}", filter);
-                            chord == filter
-                        } else {
-                            true
-                        };
-
-                        if meets_filter {
-                            //info!("Filter met");
-                            objective.is_complete = true;
-                            *draw_mode = complete_objective_draw_mode();
-                            commands
-                                .entity(event.dragged)
-                                .insert(CompletingObjective {
-                                    objective: event.target,
-                                })
-                                .insert(RigidBody::Fixed);
-                        }
+                    if orb.cluster.meets_filter(objective.filter) {
+                        //info!("Filter met");
+                        objective.is_complete = true;
+                        *draw_mode = complete_objective_draw_mode();
+                        commands
+                            .entity(event.dragged)
+                            .insert(CompletingObjective {
+                                objective: event.target,
+                            })
+                            .insert(RigidBody::Fixed);
+                        ew.send(ObjectiveCompletedEvent {
+                            cluster: orb.cluster.clone(),
+                        });
                     }
                 }
             }
@@ -151,16 +135,23 @@ fn check_for_completions(
     }
 }
 
+/// Keep objectives complete exactly when an orb is completing them, e.g. after an undo
 fn update_met_objectives(
     removals: RemovedComponents<CompletingObjective>,
+    added: Query<Added<CompletingObjective>>,
     complete_objectives: Query<&CompletingObjective>,
     mut objectives: Query<(Entity, &mut Objective, &mut DrawMode)>,
 ) {
-    if removals.iter().next().is_some() {
+    if removals.iter().next().is_some() || !added.is_empty() {
         for (obj, mut objective, mut draw_mode) in objectives.iter_mut() {
-            if objective.is_complete && !complete_objectives.iter().any(|x| x.objective == obj) {
-                objective.is_complete = false;
-                *draw_mode = incomplete_objective_draw_mode();
+            let is_complete = complete_objectives.iter().any(|x| x.objective == obj);
+            if objective.is_complete != is_complete {
+                objective.is_complete = is_complete;
+                *draw_mode = if is_complete {
+                    complete_objective_draw_mode()
+                } else {
+                    incomplete_objective_draw_mode()
+                };
             }
         }
     }
@@ -191,7 +182,7 @@ pub fn create_objective(
     commands: &mut Commands,
     index: usize,
     total_number: usize,
-    chord_option: Option<Chord>,
+    filter: ChordFilter,
 ) {
     let position_x =
         (WINDOW_WIDTH * ((index + 1) as f32) / (total_number as f32 + 1.)) - (WINDOW_WIDTH * 0.5);
@@ -230,14 +221,14 @@ pub fn create_objective(
         .insert(transform);
 
     entity_builder.insert(crate::Objective {
-        filter: chord_option,
+        filter,
         is_complete: false,
         is_hovered: false,
     });
 
     entity_builder.insert(Interactable { interacting: false });
 
-    if let Some(chord) = chord_option {
+    if let Some(chord) = filter.chord {
         let num_children = chord.intervals().len();
         let child_scale = 0.9 / (num_children as f32);
         let child_distance = if num_children <= 1 {
@@ -12,6 +12,8 @@ use rand::Rng;
 use crate::{cluster::*, components::NoteCircle};
 
 pub const SHAPE_SIZE: f32 = 60f32;
+/// How far in front of the board orbs are drawn
+pub const ORB_Z: f32 = 2.0;
 pub const ANIMATION_SECONDS: u64 = 1;
 
 pub fn create_orb_near(
@@ -35,7 +37,7 @@ pub fn create_orb_near(
         angle,
         cluster,
         existing_note_circles,
-    )
+    );
 }
 
 pub fn create_orb(
@@ -45,10 +47,10 @@ pub fn create_orb(
     angle: f32,
     cluster: Cluster,
     existing_note_circles: &mut Vec<(Entity, &NoteCircle, &GlobalTransform)>,
-) {
+) -> Entity {
     let collider_shape = Collider::ball(shape_size / 2.);
     let transform: Transform = Transform {
-        translation: position.extend(2.0),
+        translation: position.extend(ORB_Z),
         rotation: Quat::from_rotation_x(angle),
         scale: Vec3::ONE,
     };
@@ -57,7 +59,7 @@ pub fn create_orb(
 
     let mut entity_builder = commands.spawn();
 
-    let num_children = cluster.notes.len();
+    let num_children = cluster.pitches.len();
 
     entity_builder.insert_bundle(GeometryBuilder::build_as(
         &shapes::Circle {
@@ -86,14 +88,19 @@ pub fn create_orb(
         z: 1.,
     };
 
-    for note in cluster.notes.iter() {
-        let child_angle = (TAU * (note.0 as f32)) / 12.;
+    for pitch in cluster.pitches.iter() {
+        let child_angle = (TAU * (pitch.note().0 as f32)) / 12.;
+        //Higher octaves sit further from the centre, so the same note in two octaves does not overlap
+        let register = (1. + 0.4 * (pitch.octave() - Pitch::DEFAULT_OCTAVE) as f32).clamp(0.2, 1.8);
 
-        let child_translation = child_distance
+        let child_translation = (child_distance * register)
             .rotate(Vec2::from_angle(child_angle))
             .extend(5.);
 
-        if let Some(index) = existing_note_circles.iter().position(|x| &x.1.note == note) {
+        if let Some(index) = existing_note_circles
+            .iter()
+            .position(|x| &x.1.pitch == pitch)
+        {
             let (entity, _, gt) = existing_note_circles.remove(index);
 
             let start_translation = gt.translation() - transform.translation;
@@ -134,11 +141,11 @@ pub fn create_orb(
                         radius: shape_size * 0.5,
                     },
                     bevy_prototype_lyon::prelude::DrawMode::Fill(
-                        bevy_prototype_lyon::draw::FillMode::color(note.get_color()),
+                        bevy_prototype_lyon::draw::FillMode::color(pitch.get_color()),
                     ),
                     Transform::from_translation(child_translation).with_scale(child_scale_vec),
                 ))
-                .insert(NoteCircle { note: *note });
+                .insert(NoteCircle { pitch: *pitch });
             });
         }
     }
@@ -147,4 +154,6 @@ pub fn create_orb(
 
     entity_builder.insert(crate::Interactable { interacting: false });
     entity_builder.insert(crate::Draggable {});
+
+    entity_builder.id()
 }
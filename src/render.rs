@@ -0,0 +1,233 @@
+//! Renders chords to WAV files without a window or an audio device.
+//! The notes are played by the same drones as in the game, so a render sounds just like playing the chords
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use crate::cluster::*;
+use crate::instrument::Instrument;
+use crate::level::GameLevel;
+use crate::scala::*;
+use crate::sound::{DroneControl, DroneDecoder, SAMPLE_RATE};
+use crate::tuning::*;
+
+/// How chords are rendered
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub instrument: Instrument,
+    pub frequencies: PitchFrequencies,
+    /// How long each chord sounds before the next one starts, in seconds
+    pub chord_seconds: f32,
+    pub volume: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            instrument: Instrument::default(),
+            frequencies: PitchFrequencies::default(),
+            chord_seconds: 1.5,
+            volume: 1.0,
+        }
+    }
+}
+
+/// The chords to play for a level: the cluster which completes each objective in its shortest solution
+pub fn level_chords(level: &GameLevel) -> Result<Vec<Cluster>, String> {
+    Ok(level.solve().map_err(|e| e.to_string())?.completions)
+}
+
+/// The frequency of each pitch in the tuning. Scala files are read from the assets folder
+pub fn tuning_frequencies(tuning: &Tuning, assets: &Path) -> Result<PitchFrequencies, String> {
+    let (scale, mapping) = match &tuning.system {
+        TuningSystem::Scala { scale, mapping } => (scale, mapping),
+        _ => return Ok(PitchFrequencies::from(tuning)),
+    };
+
+    let read =
+        |path: &str| std::fs::read_to_string(assets.join(path)).map_err(|e| format!("{path}: {e}"));
+    let scale = Scale::parse(&read(scale)?).map_err(|e| format!("{scale}: {e}"))?;
+    let mapping = match mapping {
+        Some(path) => KeyboardMapping::parse(&read(path)?).map_err(|e| format!("{path}: {e}"))?,
+        None => KeyboardMapping::linear(tuning.reference as f64),
+    };
+
+    let mut frequencies = PitchFrequencies::from(tuning);
+    for (p, frequency) in frequencies.0.iter_mut().enumerate() {
+        if let Some(f) = mapping.frequency(p as i32, &scale) {
+            *frequency = f as f32;
+        }
+    }
+    Ok(frequencies)
+}
+
+/// Play the clusters one after another, as interleaved stereo samples at `SAMPLE_RATE`.
+/// Notes fade in and out as they do in the game, and the last chord is left to ring out
+pub fn render_clusters(clusters: &[Cluster], settings: &RenderSettings) -> Vec<f32> {
+    let chord_frames = (settings.chord_seconds * SAMPLE_RATE as f32) as usize;
+    let mut samples = Vec::new();
+    let mut drones: Vec<(Arc<DroneControl>, DroneDecoder)> = Vec::new();
+
+    for cluster in clusters {
+        for (control, _) in drones.iter() {
+            control.stop();
+        }
+
+        //Share the volume between the notes like the game does
+        let total = cluster.pitches.len().max(1);
+        for (pitch, count) in cluster.pitches.iter().counts() {
+            let control = Arc::new(DroneControl::default());
+            control.set_volume(settings.volume * count as f32 / total as f32);
+            let graph = settings.instrument.graph(settings.frequencies.get(*pitch));
+            drones.push((control.clone(), DroneDecoder::new(graph, control)));
+        }
+
+        mix(&mut drones, chord_frames, &mut samples);
+    }
+
+    for (control, _) in drones.iter() {
+        control.stop();
+    }
+    while !drones.is_empty() {
+        mix(&mut drones, SAMPLE_RATE as usize / 10, &mut samples);
+    }
+
+    samples
+}
+
+/// Add this many frames of the drones to the samples, dropping any which end
+fn mix(drones: &mut Vec<(Arc<DroneControl>, DroneDecoder)>, frames: usize, samples: &mut Vec<f32>) {
+    let start = samples.len();
+    samples.resize(start + frames * 2, 0.);
+
+    drones.retain_mut(|(_, decoder)| {
+        for sample in samples[start..].iter_mut() {
+            match decoder.next() {
+                Some(s) => *sample += s,
+                None => return false,
+            }
+        }
+        true
+    });
+}
+
+/// Write interleaved stereo samples at `SAMPLE_RATE` as a 16 bit WAV file
+pub fn write_wav(samples: &[f32], writer: &mut impl Write) -> std::io::Result<()> {
+    const CHANNELS: u16 = 2;
+    const BYTES_PER_SAMPLE: u16 = 2;
+    let data_length = (samples.len() * BYTES_PER_SAMPLE as usize) as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_length).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; //PCM
+    writer.write_all(&CHANNELS.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(SAMPLE_RATE * (CHANNELS * BYTES_PER_SAMPLE) as u32).to_le_bytes())?;
+    writer.write_all(&(CHANNELS * BYTES_PER_SAMPLE).to_le_bytes())?;
+    writer.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_length.to_le_bytes())?;
+
+    for sample in samples {
+        let value = (sample.clamp(-1., 1.) * i16::MAX as f32) as i16;
+        writer.write_all(&value.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Render the clusters and write them to a WAV file
+pub fn render_to_file(
+    clusters: &[Cluster],
+    settings: &RenderSettings,
+    path: &Path,
+) -> std::io::Result<()> {
+    let samples = render_clusters(clusters, settings);
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_wav(&samples, &mut writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound::RELEASE_SECONDS;
+
+    fn read_u16(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    fn c_major_and_a_minor() -> Vec<Cluster> {
+        ["C4+E4+G4", "A3+C4+E4"]
+            .iter()
+            .map(|chord| Cluster {
+                pitches: chord.split('+').map(|p| p.parse().unwrap()).collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wav_files_have_a_44_byte_header() {
+        let mut bytes = Vec::new();
+        write_wav(&[0., 0.5, -0.5, 2.], &mut bytes).unwrap();
+
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(read_u32(&bytes, 4), 36 + 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(read_u32(&bytes, 16), 16);
+        assert_eq!(read_u16(&bytes, 20), 1);
+        assert_eq!(read_u16(&bytes, 22), 2);
+        assert_eq!(read_u32(&bytes, 24), SAMPLE_RATE);
+        assert_eq!(read_u32(&bytes, 28), SAMPLE_RATE * 4);
+        assert_eq!(read_u16(&bytes, 32), 4);
+        assert_eq!(read_u16(&bytes, 34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(read_u32(&bytes, 40), 8);
+
+        let samples = (0..4)
+            .map(|i| read_u16(&bytes, 44 + i * 2) as i16)
+            .collect_vec();
+        assert_eq!(samples, vec![0, i16::MAX / 2, -i16::MAX / 2, i16::MAX]);
+    }
+
+    #[test]
+    fn each_chord_lasts_chord_seconds_and_the_last_rings_out() {
+        let settings = RenderSettings {
+            chord_seconds: 0.5,
+            ..Default::default()
+        };
+        let clusters = c_major_and_a_minor();
+        let samples = render_clusters(&clusters, &settings);
+
+        let chord_samples = (settings.chord_seconds * SAMPLE_RATE as f32) as usize * 2;
+        let release = samples.len() - clusters.len() * chord_samples;
+        assert_eq!(release % (SAMPLE_RATE as usize / 10 * 2), 0);
+        assert!(release >= (RELEASE_SECONDS * SAMPLE_RATE as f32) as usize * 2);
+        assert!(release <= SAMPLE_RATE as usize * 2 * 2);
+
+        let tail = &samples[samples.len() - 100..];
+        assert!(tail.iter().all(|s| s.abs() < 0.001));
+    }
+
+    #[test]
+    fn chords_can_be_heard() {
+        let samples = render_clusters(&c_major_and_a_minor(), &Default::default());
+        let peak = samples.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+
+        assert!(peak > 0.01, "the render is silent");
+        assert!(rms > 0.001, "the render is almost silent");
+        assert!(peak <= 1., "the render clips");
+        assert!(samples.iter().all(|s| s.is_finite()));
+    }
+}
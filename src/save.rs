@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+const SAVE_KEY: &str = "save";
+
+pub struct SavePlugin;
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        let save_data = SaveData::load();
+
+        app.insert_resource(CurrentLevel(save_data.current_level))
+            .insert_resource(save_data.settings.clone())
+            .insert_resource(save_data.tuning.clone())
+            .insert_resource(save_data)
+            .add_system_to_stage(CoreStage::PostUpdate, track_progress)
+            .add_system(record_level_completion)
+            .add_system(save_settings)
+            .add_system(save_tuning)
+            .add_system(handle_settings_buttons);
+    }
+}
+
+/// The player's best result on a level
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LevelRecord {
+    pub moves: usize,
+    pub seconds: f64,
+}
+
+impl LevelRecord {
+    /// Fewer moves is better. Ties are broken by time
+    pub fn is_better_than(&self, other: &Self) -> bool {
+        self.moves < other.moves || (self.moves == other.moves && self.seconds < other.seconds)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub volume: f32,
+    pub instrument: Instrument,
+    /// Whether notes are panned left and right by the position of their orb
+    pub panning: bool,
+    /// Whether notes are quieter the further their orb is from the middle of the board
+    pub distance_level: bool,
+    /// The music played when objectives and levels are completed
+    pub flourish: FlourishSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            instrument: Instrument::default(),
+            panning: true,
+            distance_level: false,
+            flourish: FlourishSettings::default(),
+        }
+    }
+}
+
+/// Everything which is remembered between sessions
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveData {
+    /// The campaign level the player was last on, which is where they resume
+    pub current_level: usize,
+    pub highest_level: usize,
+    pub level_records: BTreeMap<usize, LevelRecord>,
+    pub settings: Settings,
+    pub tuning: Tuning,
+}
+
+impl SaveData {
+    pub fn load() -> Self {
+        storage::load(SAVE_KEY)
+            .and_then(|s| ron::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Ok(s) = ron::to_string(self) {
+            storage::save(SAVE_KEY, &s);
+        }
+    }
+}
+
+fn track_progress(current_level: Res<CurrentLevel>, mut save_data: ResMut<SaveData>) {
+    if current_level.is_changed() && save_data.current_level != current_level.0 {
+        save_data.current_level = current_level.0;
+        save_data.highest_level = save_data.highest_level.max(current_level.0);
+        save_data.save();
+    }
+}
+
+fn record_level_completion(
+    mut er: EventReader<LevelCompletedEvent>,
+    mut save_data: ResMut<SaveData>,
+) {
+    for ev in er.iter() {
+        if ev.game_mode == GameMode::Campaign {
+            let record = LevelRecord {
+                moves: ev.moves,
+                seconds: ev.seconds,
+            };
+
+            let best = save_data.level_records.entry(ev.level).or_insert(record);
+            if record.is_better_than(best) {
+                *best = record;
+            }
+            save_data.save();
+        }
+    }
+}
+
+fn save_settings(settings: Res<Settings>, mut save_data: ResMut<SaveData>) {
+    if settings.is_changed() && save_data.settings != *settings {
+        save_data.settings = settings.clone();
+        save_data.save();
+    }
+}
+
+fn save_tuning(tuning: Res<Tuning>, mut save_data: ResMut<SaveData>) {
+    if tuning.is_changed() && save_data.tuning != *tuning {
+        save_data.tuning = tuning.clone();
+        save_data.save();
+    }
+}
+
+fn handle_settings_buttons(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction == Interaction::Clicked && *button == MenuButton::Mute {
+            settings.volume = if settings.volume > 0. { 0. } else { 1. };
+        }
+    }
+}
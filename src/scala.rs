@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+
+use crate::cluster::Pitch;
+use crate::*;
+
+/// The MIDI key of middle C, where the notes are played
+pub const MIDDLE_C_KEY: i32 = 60;
+/// The MIDI key of the A above middle C, which is tuned to the reference pitch
+pub const REFERENCE_KEY: i32 = 69;
+
+pub struct ScalaPlugin;
+impl Plugin for ScalaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ScaleFile>()
+            .add_asset::<KeyboardMappingFile>()
+            .init_asset_loader::<ScaleLoader>()
+            .init_asset_loader::<KeyboardMappingLoader>()
+            .init_resource::<ScalaFiles>()
+            .add_system(show_scale_errors)
+            .add_system(show_keyboard_mapping_errors);
+    }
+}
+
+/// Why a Scala file could not be read
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ScalaError {
+    /// One-based, counting comment lines
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ScalaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The lines of a Scala file with their line numbers, skipping comments
+struct ScalaLines<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    last_line: usize,
+}
+
+impl<'a> ScalaLines<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            lines: text.lines().enumerate(),
+            last_line: 0,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<(usize, &'a str)> {
+        for (i, line) in self.lines.by_ref() {
+            self.last_line = i + 1;
+            if !line.starts_with('!') {
+                return Some((i + 1, line.trim()));
+            }
+        }
+        None
+    }
+
+    /// The first word of the next line which is not blank
+    fn next_value(&mut self, name: &str) -> Result<(usize, &'a str), ScalaError> {
+        loop {
+            match self.next_line() {
+                Some((_, "")) => continue,
+                Some((line, text)) => {
+                    return Ok((line, text.split_whitespace().next().unwrap_or_default()))
+                }
+                None => {
+                    return Err(ScalaError {
+                        line: self.last_line,
+                        message: format!("the file ended before the {name}"),
+                    })
+                }
+            }
+        }
+    }
+
+    fn parse_next<T: std::str::FromStr>(&mut self, name: &str) -> Result<T, ScalaError> {
+        let (line, value) = self.next_value(name)?;
+        value.parse().map_err(|_| ScalaError {
+            line,
+            message: format!("'{value}' is not a valid {name}"),
+        })
+    }
+}
+
+/// A scale from a Scala `.scl` file
+#[derive(Clone, PartialEq, Debug)]
+pub struct Scale {
+    pub description: String,
+    /// The ratio of each degree after the first to the first degree.
+    /// The last one is the period the scale repeats at, usually 2/1
+    pub ratios: Vec<f64>,
+}
+
+impl Scale {
+    pub fn parse(text: &str) -> Result<Self, ScalaError> {
+        let mut lines = ScalaLines::new(text);
+        let description = lines
+            .next_line()
+            .map(|(_, d)| d.to_string())
+            .ok_or(ScalaError {
+                line: 1,
+                message: "the file is empty".to_string(),
+            })?;
+
+        let count: usize = lines.parse_next("number of notes")?;
+        if count == 0 {
+            return Err(ScalaError {
+                line: lines.last_line,
+                message: "the scale must have at least one note".to_string(),
+            });
+        }
+
+        let ratios = (0..count)
+            .map(|_| {
+                let (line, value) = lines.next_value("next pitch")?;
+                parse_pitch(value).map_err(|message| ScalaError { line, message })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            description,
+            ratios,
+        })
+    }
+
+    /// The number of notes before the scale repeats
+    pub fn len(&self) -> usize {
+        self.ratios.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ratios.is_empty()
+    }
+
+    /// The ratio of a degree to the first degree. Degrees outside the scale are moved by whole periods
+    pub fn ratio(&self, degree: i32) -> f64 {
+        let len = self.len() as i32;
+        let period = self.ratios[self.len() - 1];
+        let step = match degree.rem_euclid(len) {
+            0 => 1.,
+            i => self.ratios[i as usize - 1],
+        };
+        step * period.powi(degree.div_euclid(len))
+    }
+}
+
+/// Pitches with a '.' are in cents, and the rest are ratios like `3/2` or whole numbers like `2`
+fn parse_pitch(value: &str) -> Result<f64, String> {
+    let invalid = || format!("'{value}' is not a valid pitch");
+
+    let ratio = if value.contains('.') {
+        let cents: f64 = value.parse().map_err(|_| invalid())?;
+        2f64.powf(cents / 1200.)
+    } else {
+        let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+        let numerator: f64 = numerator.parse::<u64>().map_err(|_| invalid())? as f64;
+        let denominator: f64 = denominator.parse::<u64>().map_err(|_| invalid())? as f64;
+        numerator / denominator
+    };
+
+    if ratio.is_finite() && ratio > 0. {
+        Ok(ratio)
+    } else {
+        Err(format!("'{value}' is not a positive pitch"))
+    }
+}
+
+/// Which scale degree each MIDI key plays, from a Scala `.kbm` file
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyboardMapping {
+    /// The number of keys before the mapping repeats. Zero maps every key to the next degree
+    pub size: usize,
+    pub first_key: i32,
+    pub last_key: i32,
+    /// The key which plays the first degree of the scale
+    pub middle_key: i32,
+    pub reference_key: i32,
+    pub reference_frequency: f64,
+    /// The degree the mapping repeats at. Zero means the period of the scale
+    pub octave_degree: i32,
+    /// The degree for each key in the pattern, or `None` if the key plays nothing
+    pub mapping: Vec<Option<i32>>,
+}
+
+impl KeyboardMapping {
+    pub fn parse(text: &str) -> Result<Self, ScalaError> {
+        let mut lines = ScalaLines::new(text);
+
+        let size: usize = lines.parse_next("map size")?;
+        let first_key = lines.parse_next("first MIDI note")?;
+        let last_key = lines.parse_next("last MIDI note")?;
+        let middle_key = lines.parse_next("middle note")?;
+        let reference_key = lines.parse_next("reference note")?;
+        let reference_frequency: f64 = lines.parse_next("reference frequency")?;
+        if !reference_frequency.is_finite() || reference_frequency <= 0. {
+            return Err(ScalaError {
+                line: lines.last_line,
+                message: "the reference frequency must be positive".to_string(),
+            });
+        }
+        let octave_degree = lines.parse_next("formal octave degree")?;
+
+        //A mapping may stop early, in which case the remaining keys play nothing
+        let mut mapping = Vec::with_capacity(size);
+        while mapping.len() < size {
+            match lines.next_line() {
+                Some((_, "")) => {}
+                Some((_, value)) if value.starts_with(['x', 'X']) => mapping.push(None),
+                Some((line, value)) => {
+                    let value = value.split_whitespace().next().unwrap_or_default();
+                    let degree = value.parse().map_err(|_| ScalaError {
+                        line,
+                        message: format!("'{value}' is not a scale degree or 'x'"),
+                    })?;
+                    mapping.push(Some(degree));
+                }
+                None => mapping.push(None),
+            }
+        }
+
+        Ok(Self {
+            size,
+            first_key,
+            last_key,
+            middle_key,
+            reference_key,
+            reference_frequency,
+            octave_degree,
+            mapping,
+        })
+    }
+
+    /// The mapping used without a `.kbm` file: each key plays the next degree, starting from middle C,
+    /// and the A above middle C plays the reference frequency
+    pub fn linear(reference_frequency: f64) -> Self {
+        Self {
+            size: 0,
+            first_key: 0,
+            last_key: 127,
+            middle_key: MIDDLE_C_KEY,
+            reference_key: REFERENCE_KEY,
+            reference_frequency,
+            octave_degree: 0,
+            mapping: vec![],
+        }
+    }
+
+    /// The scale degree the key plays, if it plays one
+    pub fn degree(&self, key: i32, scale: &Scale) -> Option<i32> {
+        if key < self.first_key || key > self.last_key {
+            return None;
+        }
+
+        let offset = key - self.middle_key;
+        if self.size == 0 {
+            return Some(offset);
+        }
+
+        let octave_degree = match self.octave_degree {
+            0 => scale.len() as i32,
+            d => d,
+        };
+        let index = offset.rem_euclid(self.size as i32) as usize;
+        let octaves = offset.div_euclid(self.size as i32);
+
+        self.mapping[index].map(|d| d + octaves * octave_degree)
+    }
+
+    /// The frequency the key plays in the scale, in Hz
+    pub fn frequency(&self, key: i32, scale: &Scale) -> Option<f64> {
+        let degree = self.degree(key, scale)?;
+        let reference_degree = self.degree(self.reference_key, scale)?;
+
+        Some(self.reference_frequency * scale.ratio(degree) / scale.ratio(reference_degree))
+    }
+}
+
+/// A `.scl` file. If it could not be parsed this holds the error, so it can be shown in game
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "4d4a0d36-5f0b-4c3e-9a43-2b1d0f6a7c11"]
+pub struct ScaleFile(pub Result<Scale, String>);
+
+/// A `.kbm` file. If it could not be parsed this holds the error, so it can be shown in game
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "9b3e6a52-1c7d-4f08-8e25-6d0c4b9a3f72"]
+pub struct KeyboardMappingFile(pub Result<KeyboardMapping, String>);
+
+#[derive(Default)]
+pub struct ScaleLoader;
+
+impl AssetLoader for ScaleLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let scale = Scale::parse(&String::from_utf8_lossy(bytes))
+                .map_err(|err| format!("{}: {err}", load_context.path().display()));
+            load_context.set_default_asset(LoadedAsset::new(ScaleFile(scale)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scl"]
+    }
+}
+
+#[derive(Default)]
+pub struct KeyboardMappingLoader;
+
+impl AssetLoader for KeyboardMappingLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let mapping = KeyboardMapping::parse(&String::from_utf8_lossy(bytes))
+                .map_err(|err| format!("{}: {err}", load_context.path().display()));
+            load_context.set_default_asset(LoadedAsset::new(KeyboardMappingFile(mapping)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["kbm"]
+    }
+}
+
+/// The Scala files which have been asked for, by asset path, so they stay loaded
+#[derive(Default)]
+pub struct ScalaFiles {
+    pub scales: HashMap<String, Handle<ScaleFile>>,
+    pub mappings: HashMap<String, Handle<KeyboardMappingFile>>,
+}
+
+impl ScalaFiles {
+    /// Start loading the files if they have not been asked for already
+    pub fn load(&mut self, asset_server: &AssetServer, scale: &str, mapping: Option<&str>) {
+        self.scales
+            .entry(scale.to_string())
+            .or_insert_with(|| asset_server.load(scale));
+
+        if let Some(mapping) = mapping {
+            self.mappings
+                .entry(mapping.to_string())
+                .or_insert_with(|| asset_server.load(mapping));
+        }
+    }
+
+    /// The frequency of the pitch through the scale and mapping, or `None` if they are not loaded,
+    /// could not be parsed, or do not map the pitch
+    pub fn frequency(
+        &self,
+        scale: &str,
+        mapping: Option<&str>,
+        reference_frequency: f32,
+        pitch: Pitch,
+        scales: &Assets<ScaleFile>,
+        mappings: &Assets<KeyboardMappingFile>,
+    ) -> Option<f32> {
+        let scale = scales.get(self.scales.get(scale)?)?.0.as_ref().ok()?;
+        let linear;
+        let mapping = match mapping {
+            Some(path) => mappings.get(self.mappings.get(path)?)?.0.as_ref().ok()?,
+            None => {
+                linear = KeyboardMapping::linear(reference_frequency as f64);
+                &linear
+            }
+        };
+
+        mapping.frequency(pitch.0 as i32, scale).map(|f| f as f32)
+    }
+}
+
+fn show_scale_errors(
+    mut er: EventReader<AssetEvent<ScaleFile>>,
+    scales: Res<Assets<ScaleFile>>,
+    mut error_text: Query<&mut Text, With<LevelErrorText>>,
+) {
+    for ev in er.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = ev {
+            if let Some(ScaleFile(Err(err))) = scales.get(handle) {
+                show_scala_error(err, &mut error_text);
+            }
+        }
+    }
+}
+
+fn show_keyboard_mapping_errors(
+    mut er: EventReader<AssetEvent<KeyboardMappingFile>>,
+    mappings: Res<Assets<KeyboardMappingFile>>,
+    mut error_text: Query<&mut Text, With<LevelErrorText>>,
+) {
+    for ev in er.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = ev {
+            if let Some(KeyboardMappingFile(Err(err))) = mappings.get(handle) {
+                show_scala_error(err, &mut error_text);
+            }
+        }
+    }
+}
+
+fn show_scala_error(err: &str, error_text: &mut Query<&mut Text, With<LevelErrorText>>) {
+    error!("Could not load tuning file {err}");
+    for mut text in error_text.iter_mut() {
+        text.sections[0].value = err.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    fn cents(cents: f64) -> f64 {
+        2f64.powf(cents / 1200.)
+    }
+
+    /// Just intonation on the white keys, with the black keys unmapped
+    const WHITE_KEYS_KBM: &str = "! white_keys.kbm
+! Map size
+12
+! First and last MIDI notes
+0
+127
+! Middle note, reference note and frequency
+60
+69
+440.0
+! Formal octave degree
+7
+! Mapping
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+X
+6
+";
+
+    const JUST_MAJOR_SCL: &str = "! just_major.scl
+!
+Just major scale
+ 7
+!
+ 9/8
+ 5/4
+ 4/3
+ 3/2
+ 5/3
+ 15/8
+ 2/1
+";
+
+    #[test]
+    fn werckmeister_iii_is_read() {
+        let scale = Scale::parse(include_str!("../assets/tunings/werckmeister3.scl")).unwrap();
+
+        assert_eq!(
+            scale.description,
+            "Andreas Werckmeister's temperament III (1681)"
+        );
+        assert_eq!(scale.len(), 12);
+        assert_close(scale.ratio(0), 1.);
+        assert_close(scale.ratio(1), 256. / 243.);
+        assert_close(scale.ratio(2), cents(192.18));
+        assert_close(scale.ratio(12), 2.);
+        assert_close(scale.ratio(13), 2. * 256. / 243.);
+        assert_close(scale.ratio(-1), cents(1092.18) / 2.);
+    }
+
+    #[test]
+    fn pitches_are_cents_with_a_point_and_ratios_without() {
+        assert_close(parse_pitch("700.0").unwrap(), cents(700.));
+        assert_close(parse_pitch("1200.").unwrap(), 2.);
+        assert_close(parse_pitch("3/2").unwrap(), 1.5);
+        assert_close(parse_pitch("2").unwrap(), 2.);
+
+        assert!(parse_pitch("-3/2").is_err());
+        assert!(parse_pitch("three").is_err());
+        assert!(parse_pitch("0/1").is_err());
+        assert!(parse_pitch("1/0").is_err());
+    }
+
+    #[test]
+    fn scales_repeat_at_their_last_degree() {
+        //Bohlen-Pierce repeats at a twelfth rather than an octave
+        let scale = Scale::parse("Bohlen-Pierce\n2\n9/7\n3/1").unwrap();
+        assert_close(scale.ratio(2), 3.);
+        assert_close(scale.ratio(3), 27. / 7.);
+        assert_close(scale.ratio(-2), 1. / 3.);
+    }
+
+    #[test]
+    fn keyboard_mappings_skip_keys_marked_x() {
+        let scale = Scale::parse(JUST_MAJOR_SCL).unwrap();
+        let mapping = KeyboardMapping::parse(WHITE_KEYS_KBM).unwrap();
+        assert_eq!(mapping.octave_degree, 7);
+        assert_eq!(mapping.mapping.len(), 12);
+
+        let degree = |key| mapping.degree(key, &scale);
+        assert_eq!(degree(60), Some(0));
+        assert_eq!(degree(61), None);
+        assert_eq!(degree(62), Some(1));
+        assert_eq!(degree(71), Some(6));
+        assert_eq!(degree(72), Some(7));
+        assert_eq!(degree(59), Some(-1));
+        assert_eq!(degree(48), Some(-7));
+        assert_eq!(degree(70), None);
+    }
+
+    #[test]
+    fn the_reference_key_plays_the_reference_frequency() {
+        let scale = Scale::parse(JUST_MAJOR_SCL).unwrap();
+        let mapping = KeyboardMapping::parse(WHITE_KEYS_KBM).unwrap();
+        let frequency = |key| mapping.frequency(key, &scale).unwrap();
+
+        assert_close(frequency(69), 440.);
+        assert_close(frequency(60), 264.);
+        assert_close(frequency(67), 396.);
+        assert_close(frequency(72), 528.);
+        assert_eq!(mapping.frequency(61, &scale), None);
+
+        let werckmeister =
+            Scale::parse(include_str!("../assets/tunings/werckmeister3.scl")).unwrap();
+        let linear = KeyboardMapping::linear(432.);
+        assert_close(
+            linear.frequency(REFERENCE_KEY, &werckmeister).unwrap(),
+            432.,
+        );
+        assert_close(
+            linear.frequency(REFERENCE_KEY + 12, &werckmeister).unwrap(),
+            864.,
+        );
+    }
+
+    #[test]
+    fn short_mappings_leave_the_other_keys_unmapped() {
+        let mapping = KeyboardMapping::parse("3\n0\n127\n60\n60\n261.6\n0\n0\n1\n").unwrap();
+        assert_eq!(mapping.mapping, vec![Some(0), Some(1), None]);
+    }
+
+    #[test]
+    fn errors_count_comment_lines() {
+        let bad_pitch = "! bad.scl\n!\nBad scale\n 3\n! a comment\n 9/8\n five/4\n 2/1\n";
+        let err = Scale::parse(bad_pitch).unwrap_err();
+        assert_eq!(err.line, 7);
+        assert_eq!(err.to_string(), "line 7: 'five/4' is not a valid pitch");
+
+        let truncated = "! short.scl\nShort scale\n 3\n 9/8\n! the file ends here\n";
+        let err = Scale::parse(truncated).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 5: the file ended before the next pitch"
+        );
+
+        let bad_frequency = WHITE_KEYS_KBM.replace("440.0", "-440.0");
+        let err = KeyboardMapping::parse(&bad_frequency).unwrap_err();
+        assert_eq!(err.line, 10);
+
+        let bad_degree = WHITE_KEYS_KBM.replace("\nX\n", "\ny\n");
+        let err = KeyboardMapping::parse(&bad_degree).unwrap_err();
+        assert_eq!(err.to_string(), "line 24: 'y' is not a scale degree or 'x'");
+    }
+}
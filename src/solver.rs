@@ -0,0 +1,314 @@
+use std::fmt::Display;
+
+use itertools::Itertools;
+
+use crate::chord::{Chord, ChordFilter};
+use crate::cluster::*;
+
+/// The most clusters one move can combine: the dragged orb and the two orbs nearest it which it is dropped touching.
+/// `combine_orbs` ignores any others, so this covers every move the game allows
+pub const MAX_COMBINED: usize = 3;
+
+/// Something the player can do which changes the clusters in play.
+/// Deconstructing is never part of a shortest solution, see `solve`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Move {
+    /// Drop a cluster touching one or two others, combining them all
+    Combine(Vec<Cluster>),
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Move::Combine(clusters) => write!(
+                f,
+                "combine {}",
+                clusters
+                    .iter()
+                    .map(|c| format!("[{}]", c.get_notes_text()))
+                    .join(" with ")
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Solution {
+    /// The shortest sequence of moves which completes every objective
+    pub moves: Vec<Move>,
+    /// The cluster placed on each objective, in objective order
+    pub completions: Vec<Cluster>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SolverError {
+    /// No choice of notes completes every objective at once
+    Unsolvable,
+}
+
+impl Display for SolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolverError::Unsolvable => {
+                write!(f, "no combination of moves completes every objective")
+            }
+        }
+    }
+}
+
+/// The fewest moves which combine this many single notes into one cluster
+pub fn moves_to_combine(notes: usize) -> usize {
+    (notes.saturating_sub(1) + MAX_COMBINED - 2) / (MAX_COMBINED - 1)
+}
+
+/// Find the shortest solution to a level.
+///
+/// Every orb starts as a single note, and each move leaves at most one cluster of more than one note:
+/// `Cluster::combine` splits pitches the clusters share into single notes, and deconstructing only makes single notes.
+/// So each objective's cluster is built by moves of its own, which gain it at most `MAX_COMBINED - 1` notes each,
+/// and dropping single notes onto it is as quick as any other way. That leaves choosing the notes for each objective,
+/// where a cluster never needs two notes of the same pitch class, because the chord only depends on the lowest one
+pub fn solve(objectives: &[ChordFilter], notes: &[Pitch]) -> Result<Solution, SolverError> {
+    let mut available = notes.iter().copied().counts().into_iter().collect_vec();
+    available.sort();
+
+    let mut chosen = Vec::with_capacity(objectives.len());
+    let mut best: Option<(usize, Vec<Vec<Pitch>>)> = None;
+    choose_notes(objectives, &mut available, &mut chosen, 0, &mut best);
+
+    let (_, chosen) = best.ok_or(SolverError::Unsolvable)?;
+    let mut moves = Vec::new();
+    let completions = chosen
+        .iter()
+        .map(|pitches| build_cluster(pitches, &mut moves))
+        .collect();
+
+    Ok(Solution { moves, completions })
+}
+
+/// Depth first search for the cheapest notes for each remaining objective, keeping the best found so far in `best`
+fn choose_notes(
+    objectives: &[ChordFilter],
+    available: &mut Vec<(Pitch, usize)>,
+    chosen: &mut Vec<Vec<Pitch>>,
+    moves: usize,
+    best: &mut Option<(usize, Vec<Vec<Pitch>>)>,
+) {
+    let objective = match objectives.get(chosen.len()) {
+        Some(objective) => *objective,
+        None => {
+            *best = Some((moves, chosen.clone()));
+            return;
+        }
+    };
+
+    let remaining = objectives.len() - chosen.len();
+    for pitches in candidates(objective, available) {
+        let moves = moves + moves_to_combine(pitches.len());
+        if best
+            .as_ref()
+            .map(|b| moves + remaining - 1 >= b.0)
+            .unwrap_or(false)
+        {
+            //Every objective takes at least one move, and candidates are cheapest first
+            break;
+        }
+
+        set_used(available, &pitches, true);
+        chosen.push(pitches);
+        choose_notes(objectives, available, chosen, moves, best);
+        let pitches = chosen.pop().unwrap_or_default();
+        set_used(available, &pitches, false);
+    }
+}
+
+/// Every set of available pitches, with one of each pitch class, which meets the filter. Smallest first
+fn candidates(filter: ChordFilter, available: &[(Pitch, usize)]) -> Vec<Vec<Pitch>> {
+    let chords = match filter.chord {
+        Some(chord) => vec![chord],
+        None => Chord::all().values().copied().collect_vec(),
+    };
+    let roots = match filter.root {
+        Some(root) => vec![root],
+        None => (0..12).map(Note).collect_vec(),
+    };
+
+    chords
+        .into_iter()
+        .cartesian_product(roots)
+        .flat_map(|(chord, root)| {
+            chord
+                .get_notes(root)
+                .into_iter()
+                .map(|note| {
+                    available
+                        .iter()
+                        .filter(|(p, count)| *count > 0 && p.note() == note)
+                        .map(|(p, _)| *p)
+                        .collect_vec()
+                })
+                .multi_cartesian_product()
+        })
+        .map(|pitches| pitches.into_iter().sorted().collect_vec())
+        .filter(|pitches| {
+            Cluster {
+                pitches: pitches.iter().copied().collect(),
+            }
+            .meets_filter(filter)
+        })
+        .unique()
+        .sorted_by_key(|pitches| pitches.len())
+        .collect()
+}
+
+fn set_used(available: &mut [(Pitch, usize)], pitches: &[Pitch], used: bool) {
+    for (pitch, count) in available.iter_mut() {
+        if pitches.contains(pitch) {
+            if used {
+                *count -= 1;
+            } else {
+                *count += 1;
+            }
+        }
+    }
+}
+
+/// Combine single notes into a cluster of these pitches, dropping up to `MAX_COMBINED - 1` notes onto it at a time
+fn build_cluster(pitches: &[Pitch], moves: &mut Vec<Move>) -> Cluster {
+    let mut singles = pitches.iter().map(|&p| Cluster::from(p));
+    let mut clusters = singles.next().into_iter().collect_vec();
+
+    loop {
+        let combined = clusters
+            .iter()
+            .cloned()
+            .chain(singles.by_ref().take(MAX_COMBINED - clusters.len()))
+            .collect_vec();
+        if combined.len() == clusters.len() {
+            break;
+        }
+
+        clusters = Cluster::combine(&combined);
+        moves.push(Move::Combine(combined));
+    }
+
+    clusters.into_iter().next().unwrap_or(Cluster {
+        pitches: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chord::ChordSymbol;
+
+    fn pitches(notes: &[Note]) -> Vec<Pitch> {
+        notes.iter().map(|&n| Pitch::new(n, 4)).collect()
+    }
+
+    #[test]
+    fn a_triad_is_combined_in_one_move() {
+        let notes = pitches(&[Note::C, Note::E, Note::G, Note::D]);
+        let solution = solve(&[Chord::Major.into()], &notes).unwrap();
+
+        assert_eq!(
+            solution.moves,
+            vec![Move::Combine(
+                notes[..3].iter().map(|&p| Cluster::from(p)).collect()
+            )]
+        );
+        assert_eq!(
+            solution.completions,
+            vec![Cluster {
+                pitches: notes[..3].iter().copied().collect()
+            }]
+        );
+    }
+
+    #[test]
+    fn seventh_chords_take_two_moves() {
+        let notes = pitches(&[Note::G, Note::B, Note::D, Note::F]);
+        let solution = solve(&[Chord::Dominant7.into()], &notes).unwrap();
+
+        assert_eq!(solution.moves.len(), moves_to_combine(4));
+        assert_eq!(solution.moves.len(), 2);
+    }
+
+    #[test]
+    fn missing_notes_are_unsolvable() {
+        let notes = pitches(&[Note::C, Note::D]);
+        assert_eq!(
+            solve(&[Chord::Major.into()], &notes).unwrap_err(),
+            SolverError::Unsolvable
+        );
+    }
+
+    #[test]
+    fn objectives_cannot_share_notes() {
+        let objectives = [Chord::Major.into(), Chord::Minor.into()];
+        let notes = pitches(&[Note::C, Note::E, Note::G, Note::B]);
+        assert_eq!(
+            solve(&objectives, &notes).unwrap_err(),
+            SolverError::Unsolvable
+        );
+
+        let notes = pitches(&[Note::C, Note::E, Note::G, Note::B, Note::E, Note::G]);
+        let solution = solve(&objectives, &notes).unwrap();
+        assert_eq!(solution.moves.len(), 2);
+    }
+
+    #[test]
+    fn the_cheapest_chord_is_chosen_for_any_chord() {
+        let notes = pitches(&[Note::C, Note::E, Note::G, Note::B, Note::D]);
+        let solution = solve(&[None.into(), Chord::Power.into()], &notes).unwrap();
+
+        assert_eq!(solution.moves.len(), 2);
+        assert!(solution.completions.iter().all(|c| c.pitches.len() <= 3));
+    }
+
+    #[test]
+    fn moves_combine_clusters_as_the_game_does() {
+        let notes = pitches(&[
+            Note::C,
+            Note::E,
+            Note::G,
+            Note::BB,
+            Note::D,
+            Note::F,
+            Note::A,
+        ]);
+        let solution = solve(&[Chord::Dominant13.into()], &notes).unwrap();
+        assert_eq!(solution.moves.len(), moves_to_combine(7));
+
+        //Each move after the first drops notes onto the cluster the one before made
+        let mut made = None;
+        for Move::Combine(clusters) in solution.moves.iter() {
+            assert!(clusters.len() <= MAX_COMBINED);
+            if let Some(made) = made {
+                assert_eq!(clusters[0], made);
+            }
+            let combined = Cluster::combine(clusters);
+            assert_eq!(combined.len(), 1);
+            made = combined.into_iter().next();
+        }
+        assert_eq!(made, Some(solution.completions[0].clone()));
+    }
+
+    #[test]
+    fn the_bass_decides_the_inversion() {
+        let notes = vec![
+            Pitch::new(Note::C, 4),
+            Pitch::new(Note::E, 4),
+            Pitch::new(Note::G, 4),
+            Pitch::new(Note::E, 3),
+        ];
+        let first_inversion: ChordFilter =
+            "C/E".parse::<ChordSymbol>().unwrap().try_into().unwrap();
+        let solution = solve(&[first_inversion], &notes).unwrap();
+
+        assert_eq!(
+            solution.completions[0].pitches.as_slice(),
+            &[notes[3], notes[0], notes[2]]
+        );
+    }
+}
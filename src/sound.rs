@@ -1,162 +1,543 @@
-use crate::{cluster::*, events::*};
-use bevy::{audio::AudioSink, prelude::*};
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc,
+};
+
+use crate::{
+    cluster::*,
+    components::{Interactable, Orb},
+    events::*,
+    instrument::Instrument,
+    level::LevelInstrument,
+    save::Settings,
+    tuning::PitchFrequencies,
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use bevy::{
+    audio::{AddAudioSource, Decodable},
+    prelude::*,
+    reflect::TypeUuid,
+};
 use bevy_fundsp::prelude::*;
 use itertools::Itertools;
 
-pub struct SoundPlugin;
+#[derive(Default)]
+pub struct SoundPlugin {
+    pub output: SoundOutput,
+}
+
 impl Plugin for SoundPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(DspPlugin)
-            .add_startup_system(init_dsp.label("init_dsp"))
-            .add_startup_system_to_stage(
-                StartupStage::PostStartup,
-                start_all_sounds.label("start_all_sounds"),
-            )
+        match self.output {
+            SoundOutput::Device => {
+                app.add_audio_source::<PitchDrone>()
+                    .add_system(handle_spatial_input);
+            }
+            SoundOutput::Recording => {
+                app.init_resource::<SoundRecording>()
+                    .add_system_to_stage(CoreStage::PostUpdate, record_sounds.after("set_sounds"));
+            }
+        }
+
+        //The save, level and tuning plugins replace these, but the sounds can be played without them
+        app.init_resource::<Settings>()
+            .init_resource::<LevelInstrument>()
+            .init_resource::<PitchFrequencies>()
             .init_resource::<NoteHandles>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                build_sounds
+                    .after("update_note_frequencies")
+                    .before("set_sounds"),
+            )
             .add_system_to_stage(
                 CoreStage::PostUpdate,
                 set_sounds
                     .label("set_sounds")
                     .after("track_notes_playing_changes"),
-            );
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, set_positions.after("set_sounds"));
     }
 }
 
-fn pad_sound(hz: f32) -> impl AudioUnit32 {
-    (triangle_hz(hz) + sine_hz(hz * 2.)) >> lowpole_hz(100.0) >> (split::<U2>() * 0.2)
+/// Where the sounds go
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SoundOutput {
+    /// Play the sounds on the audio device
+    #[default]
+    Device,
+    /// Play nothing and record each change of volume in `SoundRecording` instead.
+    /// This needs no audio device, `AudioPlugin` or keyboard input, so it suits tests and CI.
+    /// The notes to play still come from `EventsPlugin` and `NotesPlayingPlugin`
+    Recording,
 }
 
-fn pad_sound_note(note: Note) -> impl AudioUnit32 {
-    pad_sound(note.get_frequency())
-}
+/// The rate the drones are synthesised at, in samples per second
+pub const SAMPLE_RATE: u32 = 44100;
+/// Roughly how long a note takes to fade in when it starts sounding, in seconds
+pub const ATTACK_SECONDS: f32 = 0.05;
+/// Roughly how long a note takes to fade out when it stops sounding, in seconds
+pub const RELEASE_SECONDS: f32 = 0.3;
+/// Below this a note can't be heard, so it is treated as silent
+const SILENCE: f32 = 0.0001;
+/// How much quieter a note is at the edge of the board than in the middle, when the level depends on distance
+const DISTANCE_ATTENUATION: f32 = 0.6;
 
-//TODO fix horrible hack
-fn sound0() -> impl AudioUnit32 {
-    pad_sound_note(Note(0))
-}
-fn sound1() -> impl AudioUnit32 {
-    pad_sound_note(Note(1))
-}
-fn sound2() -> impl AudioUnit32 {
-    pad_sound_note(Note(2))
+/// Lets the game change the volume of a drone while the audio thread plays it
+#[derive(Default, Debug)]
+pub struct DroneControl {
+    volume: AtomicU32,
+    pan: AtomicU32,
+    attenuation: AtomicU32,
+    stopped: AtomicBool,
 }
-fn sound3() -> impl AudioUnit32 {
-    pad_sound_note(Note(3))
-}
-fn sound4() -> impl AudioUnit32 {
-    pad_sound_note(Note(4))
+
+impl DroneControl {
+    /// The drone eases towards this volume rather than jumping to it
+    pub fn set_volume(&self, volume: f32) {
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    /// Where the drone is heard, from -1 on the left to 1 on the right
+    pub fn set_pan(&self, pan: f32) {
+        self.pan
+            .store(pan.clamp(-1., 1.).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn pan(&self) -> f32 {
+        f32::from_bits(self.pan.load(Ordering::Relaxed))
+    }
+
+    /// How much quieter the drone is than its volume, from 0 for not at all to 1 for silent
+    pub fn set_attenuation(&self, attenuation: f32) {
+        self.attenuation
+            .store(attenuation.clamp(0., 1.).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn attenuation(&self) -> f32 {
+        f32::from_bits(self.attenuation.load(Ordering::Relaxed))
+    }
+
+    /// Fade the drone out and then end it
+    pub fn stop(&self) {
+        self.set_volume(0.);
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
 }
-fn sound5() -> impl AudioUnit32 {
-    pad_sound_note(Note(5))
+
+/// The sound of one pitch, synthesised as it plays so it never loops
+#[derive(TypeUuid)]
+#[uuid = "2f0c7c1e-8a4d-4b6e-b3f5-91d2e7a4c058"]
+pub struct PitchDrone {
+    pub frequency: f32,
+    pub instrument: Instrument,
+    pub control: Arc<DroneControl>,
 }
-fn sound6() -> impl AudioUnit32 {
-    pad_sound_note(Note(6))
+
+impl Decodable for PitchDrone {
+    type DecoderItem = f32;
+    type Decoder = DroneDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        DroneDecoder::new(self.instrument.graph(self.frequency), self.control.clone())
+    }
 }
-fn sound7() -> impl AudioUnit32 {
-    pad_sound_note(Note(7))
+
+/// Plays a stereo graph, easing towards the volume set on its control so notes start and stop without clicks
+pub struct DroneDecoder {
+    graph: Box<dyn AudioUnit32>,
+    control: Arc<DroneControl>,
+    gain: f32,
+    pan: f32,
+    attack: f32,
+    release: f32,
+    /// The right channel of the current frame, once the left has been played
+    right: Option<f32>,
+    /// Whether the drone has faded to nothing since it last sounded
+    silent: bool,
 }
-fn sound8() -> impl AudioUnit32 {
-    pad_sound_note(Note(8))
+
+impl DroneDecoder {
+    pub fn new(mut graph: Box<dyn AudioUnit32>, control: Arc<DroneControl>) -> Self {
+        graph.reset(Some(SAMPLE_RATE as f64));
+
+        Self {
+            graph,
+            control,
+            gain: 0.,
+            pan: 0.,
+            attack: smoothing(ATTACK_SECONDS),
+            release: smoothing(RELEASE_SECONDS),
+            right: None,
+            silent: true,
+        }
+    }
 }
-fn sound9() -> impl AudioUnit32 {
-    pad_sound_note(Note(9))
+
+/// How far to move towards the target volume each sample, so that it is about 95% of the way there after this long
+fn smoothing(seconds: f32) -> f32 {
+    1. - (-3. / (seconds * SAMPLE_RATE as f32)).exp()
 }
-fn sound10() -> impl AudioUnit32 {
-    pad_sound_note(Note(10))
+
+impl Iterator for DroneDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+
+        let target = self.control.volume() * (1. - self.control.attenuation());
+        if self.gain < SILENCE && target < SILENCE {
+            if self.control.is_stopped() {
+                return None;
+            }
+            //Nothing can be heard, so don't spend time running the graph
+            self.gain = 0.;
+            self.silent = true;
+            self.right = Some(0.);
+            return Some(0.);
+        }
+
+        if self.silent {
+            //Start the instrument's envelopes again, so plucked and struck notes sound each time
+            self.graph.reset(None);
+            self.pan = self.control.pan();
+            self.silent = false;
+        }
+
+        let rate = if target > self.gain {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain += (target - self.gain) * rate;
+        self.pan += (self.control.pan() - self.pan) * self.attack;
+
+        //Panning turns the far side down, so a centred note is as loud as before
+        let (left, right) = self.graph.get_stereo();
+        self.right = Some(right * self.gain * (1. + self.pan).min(1.));
+        Some(left * self.gain * (1. - self.pan).min(1.))
+    }
 }
-fn sound11() -> impl AudioUnit32 {
-    pad_sound_note(Note(11))
+
+impl rodio::Source for DroneDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
 }
 
-fn init_dsp(mut dsp_manager: ResMut<DspManager>) {
-    // length is in seconds
+/// Start a silent drone for the pitch in the current tuning.
+/// Without audio the drone is never played, but its control still records the volume it would have
+pub fn start_pitch_sound(
+    pitch: Pitch,
+    frequencies: &PitchFrequencies,
+    instrument: Instrument,
+    audio: Option<(&Audio<PitchDrone>, &mut Assets<PitchDrone>)>,
+) -> Arc<DroneControl> {
+    let control = Arc::new(DroneControl::default());
+    if let Some((audio, drones)) = audio {
+        let drone = drones.add(PitchDrone {
+            frequency: frequencies.get(pitch),
+            instrument,
+            control: control.clone(),
+        });
+        audio.play(drone);
+    }
 
-    let len = 5.0; //Note the length has a big impact on the loading time
-                   //TODO fix horrible hack
-    dsp_manager
-        .add_graph(sound0, len)
-        .add_graph(sound1, len)
-        .add_graph(sound2, len)
-        .add_graph(sound3, len)
-        .add_graph(sound4, len)
-        .add_graph(sound5, len)
-        .add_graph(sound6, len)
-        .add_graph(sound7, len)
-        .add_graph(sound8, len)
-        .add_graph(sound9, len)
-        .add_graph(sound10, len)
-        .add_graph(sound11, len);
+    control
 }
 
-fn start_all_sounds(
-    dsp_assets: Res<DspAssets>,
-    audio: Res<Audio>,
-    audio_sinks: Res<Assets<AudioSink>>,
+/// Make sure there is a sound for every pitch on the board, building them as orbs appear.
+/// When the tuning or instrument changes every sound fades out and is replaced
+fn build_sounds(
+    frequencies: Res<PitchFrequencies>,
+    settings: Res<Settings>,
+    level_instrument: Res<LevelInstrument>,
+    mut current_instrument: Local<Option<Instrument>>,
+    orbs: Query<&Orb>,
+    added_orbs: Query<&Orb, Added<Orb>>,
+    audio: Option<Res<Audio<PitchDrone>>>,
+    mut drones: Option<ResMut<Assets<PitchDrone>>>,
     mut note_handles: ResMut<NoteHandles>,
 ) {
-    let settings = PlaybackSettings {
-        repeat: true,
-        volume: 0.0,
-        ..Default::default()
+    let instrument = level_instrument.0.unwrap_or(settings.instrument);
+    let instrument_changed = *current_instrument != Some(instrument);
+    *current_instrument = Some(instrument);
+
+    let new_orbs = if frequencies.is_changed() || instrument_changed {
+        for (_, control) in note_handles.handles.drain() {
+            control.stop();
+        }
+        orbs.iter().collect_vec()
+    } else {
+        added_orbs.iter().collect_vec()
     };
 
-    //Fix horrible hack
-    let handles = [
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound0), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound1), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound2), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound3), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound4), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound5), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound6), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound7), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound8), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound9), settings.clone())),
-        audio_sinks
-            .get_handle(audio.play_with_settings(dsp_assets.graph(&sound10), settings.clone())),
-        audio_sinks.get_handle(audio.play_with_settings(dsp_assets.graph(&sound11), settings)),
-    ];
-
-    note_handles.handles = Some(handles);
+    for pitch in new_orbs.into_iter().flat_map(|o| o.cluster.pitches.iter()) {
+        if !note_handles.handles.contains_key(pitch) {
+            let output = audio.as_deref().zip(drones.as_deref_mut());
+            let control = start_pitch_sound(*pitch, &frequencies, instrument, output);
+            note_handles.handles.insert(*pitch, control);
+        }
+    }
 }
 
+/// The drone for each pitch which has been needed
 #[derive(Default)]
 pub struct NoteHandles {
-    pub handles: Option<[Handle<AudioSink>; 12]>,
+    pub handles: HashMap<Pitch, Arc<DroneControl>>,
 }
 
 fn set_sounds(
     mut er: EventReader<NotesPlayingChangedEvent>,
     note_handles: Res<NoteHandles>,
-    audio_sinks: ResMut<Assets<AudioSink>>,
+    settings: Res<Settings>,
+    mut pitches_playing: Local<Vec<Pitch>>,
 ) {
     if let Some(ev) = er.iter().last() {
-        if let Some(handles) = &note_handles.handles {
-            //something has changed. Reset all volumes
-            let counts = ev.notes.iter().counts();
+        *pitches_playing = ev.pitches.clone();
+    } else if !settings.is_changed() && !note_handles.is_changed() {
+        return;
+    }
 
-            let total: usize = counts.values().sum();
+    //something has changed. Reset all volumes
+    let counts = pitches_playing.iter().counts();
+    let total: usize = counts.values().sum::<usize>().max(1);
 
-            for n in Note::ALL_NOTES {
-                let c = *counts.get(&n).unwrap_or(&0);
-                let vol = (c as f32) / total as f32;
+    for (pitch, control) in note_handles.handles.iter() {
+        let c = *counts.get(pitch).unwrap_or(&0);
+        control.set_volume(settings.volume * (c as f32) / total as f32);
+    }
+}
 
-                let handle = &handles[n.0 as usize];
+/// Pan each sounding pitch by where its orbs are on the board, and turn it down the further they are from the middle
+fn set_positions(
+    settings: Res<Settings>,
+    note_handles: Res<NoteHandles>,
+    orbs: Query<(&Orb, &Interactable, &GlobalTransform)>,
+) {
+    let mut positions = HashMap::<Pitch, Vec<Vec2>>::new();
+    for (orb, _, transform) in orbs.iter().filter(|(_, i, _)| i.interacting) {
+        for pitch in orb.cluster.pitches.iter() {
+            positions
+                .entry(*pitch)
+                .or_default()
+                .push(transform.translation().truncate());
+        }
+    }
 
-                if let Some(sink) = audio_sinks.get(handle) {
-                    sink.set_volume(vol);
-                }
-            }
+    for (pitch, control) in note_handles.handles.iter() {
+        //Keep notes which have stopped where they were, so they fade out in place
+        if let Some(positions) = positions.get(pitch) {
+            let (pan, attenuation) = spatial_mix(positions, &settings);
+            control.set_pan(pan);
+            control.set_attenuation(attenuation);
         }
     }
 }
+
+/// The pan and attenuation of a pitch whose orbs are at these positions.
+/// Each pitch has one drone, so the pan is the average of the orbs'. Orbs with the same pitch on opposite sides
+/// are heard from the middle, as the same sound coming from both sides would be. The attenuation is the average of each orb's,
+/// so they are not heard as if they were in the middle
+fn spatial_mix(positions: &[Vec2], settings: &Settings) -> (f32, f32) {
+    let count = positions.len().max(1) as f32;
+
+    let pan = if settings.panning {
+        positions.iter().map(|p| p.x).sum::<f32>() / count / (WINDOW_WIDTH * 0.5)
+    } else {
+        0.
+    };
+    let attenuation = if settings.distance_level {
+        positions
+            .iter()
+            .map(|p| DISTANCE_ATTENUATION * (p.length() / (WINDOW_HEIGHT * 0.5)).min(1.))
+            .sum::<f32>()
+            / count
+    } else {
+        0.
+    };
+
+    (pan.clamp(-1., 1.), attenuation)
+}
+
+fn handle_spatial_input(keys: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if !keys.just_pressed(KeyCode::P) {
+        return;
+    }
+
+    if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        settings.distance_level = !settings.distance_level;
+        info!("Distance level: {}", settings.distance_level);
+    } else {
+        settings.panning = !settings.panning;
+        info!("Panning: {}", settings.panning);
+    }
+}
+
+/// A change in the volume of a pitch, recorded instead of being played
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VolumeChange {
+    /// Seconds since startup
+    pub time: f64,
+    pub pitch: Pitch,
+    pub volume: f32,
+}
+
+/// Every change of volume, when the sound output is `SoundOutput::Recording`
+#[derive(Default, Debug)]
+pub struct SoundRecording {
+    pub changes: Vec<VolumeChange>,
+    volumes: HashMap<Pitch, f32>,
+}
+
+impl SoundRecording {
+    /// The volume of the pitch as last set
+    pub fn volume(&self, pitch: Pitch) -> f32 {
+        *self.volumes.get(&pitch).unwrap_or(&0.)
+    }
+
+    /// The total volume of the note in every octave, as last set
+    pub fn note_volume(&self, note: Note) -> f32 {
+        self.volumes
+            .iter()
+            .filter(|(pitch, _)| pitch.note() == note)
+            .map(|(_, volume)| volume)
+            .sum()
+    }
+
+    /// The notes which are sounding, in order
+    pub fn sounding_notes(&self) -> Vec<Note> {
+        self.volumes
+            .iter()
+            .filter(|(_, volume)| **volume > 0.)
+            .map(|(pitch, _)| pitch.note())
+            .sorted()
+            .dedup()
+            .collect_vec()
+    }
+
+    /// The volume of the note in every octave at each time it changed
+    pub fn note_history(&self, note: Note) -> Vec<(f64, f32)> {
+        let mut volumes = HashMap::<Pitch, f32>::new();
+        self.changes
+            .iter()
+            .filter(|change| change.pitch.note() == note)
+            .map(|change| {
+                volumes.insert(change.pitch, change.volume);
+                (change.time, volumes.values().sum())
+            })
+            .collect_vec()
+    }
+}
+
+fn record_sounds(
+    time: Res<Time>,
+    note_handles: Res<NoteHandles>,
+    mut recording: ResMut<SoundRecording>,
+) {
+    let time = time.seconds_since_startup();
+
+    //Drones which have been replaced fall silent
+    let stopped = recording
+        .volumes
+        .keys()
+        .filter(|pitch| !note_handles.handles.contains_key(pitch))
+        .copied()
+        .collect_vec();
+    let volumes = stopped.into_iter().map(|pitch| (pitch, 0.)).chain(
+        note_handles
+            .handles
+            .iter()
+            .map(|(pitch, control)| (*pitch, control.volume())),
+    );
+
+    for (pitch, volume) in volumes.sorted_by_key(|(pitch, _)| *pitch) {
+        if recording.volume(pitch) != volume {
+            recording.volumes.insert(pitch, volume);
+            recording.changes.push(VolumeChange {
+                time,
+                pitch,
+                volume,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    fn spatial_settings() -> Settings {
+        Settings {
+            panning: true,
+            distance_level: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn orbs_are_panned_by_their_position() {
+        let edge = WINDOW_WIDTH * 0.5;
+        let settings = spatial_settings();
+
+        assert_close(spatial_mix(&[Vec2::new(-edge, 0.)], &settings).0, -1.);
+        assert_close(spatial_mix(&[Vec2::new(edge / 2., 0.)], &settings).0, 0.5);
+        assert_close(spatial_mix(&[Vec2::new(edge * 2., 0.)], &settings).0, 1.);
+        assert_eq!(spatial_mix(&[Vec2::ZERO], &settings), (0., 0.));
+    }
+
+    #[test]
+    fn the_same_pitch_on_opposite_sides_is_heard_from_the_middle_but_not_as_near() {
+        let edge = WINDOW_WIDTH * 0.5;
+        let positions = [Vec2::new(-edge, 0.), Vec2::new(edge, 0.)];
+        let (pan, attenuation) = spatial_mix(&positions, &spatial_settings());
+
+        assert_close(pan, 0.);
+        assert_close(
+            attenuation,
+            spatial_mix(&positions[..1], &spatial_settings()).1,
+        );
+        assert!(attenuation > 0.);
+    }
+
+    #[test]
+    fn spatial_settings_can_be_turned_off() {
+        let positions = [Vec2::new(100., 200.)];
+        let settings = Settings {
+            panning: false,
+            distance_level: false,
+            ..Default::default()
+        };
+        assert_eq!(spatial_mix(&positions, &settings), (0., 0.));
+    }
+}
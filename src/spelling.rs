@@ -0,0 +1,368 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chord::{ChordAnalysis, ChordSymbol};
+use crate::cluster::Note;
+
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+const NATURALS: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+/// A note written as a letter plus sharps or flats, e.g. G# rather than Ab
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SpelledNote {
+    /// C is 0, B is 6
+    pub letter: u8,
+    /// Positive for sharps, negative for flats
+    pub accidental: i8,
+}
+
+impl SpelledNote {
+    /// Write the note with this letter, adding whatever sharps or flats are needed
+    pub fn new(note: Note, letter: u8) -> Self {
+        let letter = letter % 7;
+        let difference = (note.0 as i8 - NATURALS[letter as usize] as i8).rem_euclid(12);
+        let accidental = if difference > 6 {
+            difference - 12
+        } else {
+            difference
+        };
+
+        Self { letter, accidental }
+    }
+
+    /// Natural notes are written as naturals and the rest with a sharp
+    pub fn sharp(note: Note) -> Self {
+        let letter = NATURALS
+            .iter()
+            .rposition(|&n| n <= note.0 % 12)
+            .unwrap_or(0);
+        Self::new(note, letter as u8)
+    }
+
+    /// Natural notes are written as naturals and the rest with a flat
+    pub fn flat(note: Note) -> Self {
+        let letter = NATURALS.iter().position(|&n| n >= note.0 % 12).unwrap_or(0);
+        Self::new(note, letter as u8)
+    }
+
+    pub fn note(&self) -> Note {
+        Note(self.semitones().rem_euclid(12) as u8)
+    }
+
+    /// Semitones above the C with the same octave number, so Cb is -1 and B# is 12
+    pub fn semitones(&self) -> i8 {
+        NATURALS[self.letter as usize] as i8 + self.accidental
+    }
+
+    /// The notes of the major scale starting on this note, spelled with one of each letter
+    pub fn major_scale(&self) -> [SpelledNote; 7] {
+        const MAJOR_SCALE: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+        let tonic = self.note();
+        std::array::from_fn(|i| {
+            SpelledNote::new(Note((tonic.0 + MAJOR_SCALE[i]) % 12), self.letter + i as u8)
+        })
+    }
+
+    /// Whether the key signature of the major key starting on this note has sharps
+    pub fn has_sharps(&self) -> bool {
+        self.major_scale().iter().map(|n| n.accidental).sum::<i8>() > 0
+    }
+
+    /// Spell a note as it is written in the major key starting on `key`.
+    /// Notes which are not in the key are written with sharps in sharp keys and with flats otherwise
+    pub fn in_key(note: Note, key: Option<SpelledNote>) -> Self {
+        match key {
+            Some(key) => key
+                .major_scale()
+                .into_iter()
+                .find(|n| n.note() == note)
+                .unwrap_or_else(|| {
+                    if key.has_sharps() {
+                        Self::sharp(note)
+                    } else {
+                        Self::flat(note)
+                    }
+                }),
+            None => Self::flat(note),
+        }
+    }
+}
+
+impl std::fmt::Display for SpelledNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let accidental = if self.accidental > 0 { "#" } else { "b" };
+        write!(
+            f,
+            "{}{}",
+            LETTERS[self.letter as usize],
+            accidental.repeat(self.accidental.unsigned_abs() as usize)
+        )
+    }
+}
+
+impl FromStr for SpelledNote {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let note = s.parse::<Note>()?;
+        let first = s.trim().chars().next().map(|c| c.to_ascii_uppercase());
+        let letter = LETTERS
+            .iter()
+            .position(|&l| Some(l) == first)
+            .ok_or_else(|| format!("'{s}' is not a note name"))?;
+
+        Ok(Self::new(note, letter as u8))
+    }
+}
+
+impl TryFrom<String> for SpelledNote {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SpelledNote> for String {
+    fn from(note: SpelledNote) -> Self {
+        note.to_string()
+    }
+}
+
+impl ChordSymbol {
+    /// Spell the root as it was written, if the symbol was parsed.
+    /// Otherwise spell it as it is written in the key, unless that would need double sharps or flats,
+    /// or failing that with whichever of sharps and flats needs fewer of them for the whole chord
+    pub fn spell_root(&self, key: Option<SpelledNote>) -> SpelledNote {
+        if let Some(root) = self.spelled_root {
+            return root;
+        }
+
+        if let Some(root) = key
+            .into_iter()
+            .flat_map(|k| k.major_scale())
+            .find(|n| n.note() == self.root)
+        {
+            if self
+                .chord
+                .intervals()
+                .iter()
+                .all(|&i| self.spell_interval(root, i).accidental.abs() <= 1)
+            {
+                return root;
+            }
+        }
+
+        let prefer_sharps = key.map(|k| k.has_sharps()).unwrap_or(false);
+        let (first, second) = if prefer_sharps {
+            (SpelledNote::sharp(self.root), SpelledNote::flat(self.root))
+        } else {
+            (SpelledNote::flat(self.root), SpelledNote::sharp(self.root))
+        };
+
+        let accidentals = |root: SpelledNote| -> u32 {
+            self.chord
+                .intervals()
+                .iter()
+                .map(|&i| self.spell_interval(root, i).accidental.unsigned_abs() as u32)
+                .sum()
+        };
+
+        if accidentals(second) < accidentals(first) {
+            second
+        } else {
+            first
+        }
+    }
+
+    /// Spell a note of the chord from the root, so thirds are written as thirds and fifths as fifths.
+    /// Notes which are not in the chord are spelled in the key
+    pub fn spell_note(&self, note: Note, key: Option<SpelledNote>) -> SpelledNote {
+        let interval = (note.0 + 12 - self.root.0) % 12;
+        if self.chord.intervals().contains(&interval) {
+            self.spell_interval(self.spell_root(key), interval)
+        } else {
+            SpelledNote::in_key(note, key)
+        }
+    }
+
+    fn spell_interval(&self, root: SpelledNote, interval: u8) -> SpelledNote {
+        SpelledNote::new(
+            Note((self.root.0 + interval) % 12),
+            root.letter + self.chord.letter_offset(interval),
+        )
+    }
+
+    /// The chord symbol, spelled to suit the key
+    pub fn spelled_name(&self, key: Option<SpelledNote>) -> String {
+        let mut name = format!("{}{}", self.spell_root(key), self.chord.symbol());
+        if let Some(bass) = self.bass {
+            let bass = self
+                .spelled_bass
+                .unwrap_or_else(|| self.spell_note(bass, key));
+            name.push_str(&format!("/{bass}"));
+        }
+        name
+    }
+}
+
+impl ChordAnalysis {
+    /// The notes, in order, spelled as part of this chord
+    pub fn spell_notes(&self, notes: &[Note], key: Option<SpelledNote>) -> Vec<SpelledNote> {
+        let symbol = ChordSymbol::from(*self);
+        notes.iter().map(|&n| symbol.spell_note(n, key)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chord::Chord;
+    use strum::IntoEnumIterator;
+
+    fn note(name: &str) -> SpelledNote {
+        name.parse().unwrap()
+    }
+
+    fn names(notes: impl IntoIterator<Item = SpelledNote>) -> String {
+        notes
+            .into_iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn unspelled(root: Note, chord: Chord) -> ChordSymbol {
+        ChordSymbol {
+            root,
+            chord,
+            bass: None,
+            spelled_root: None,
+            spelled_bass: None,
+        }
+    }
+
+    fn chord_notes(symbol: &ChordSymbol, key: Option<SpelledNote>) -> String {
+        names(
+            symbol
+                .chord
+                .intervals()
+                .iter()
+                .map(|&i| symbol.spell_note(Note((symbol.root.0 + i) % 12), key)),
+        )
+    }
+
+    #[test]
+    fn every_key_signature_has_one_of_each_letter() {
+        for (key, scale) in [
+            ("Cb", "Cb Db Eb Fb Gb Ab Bb"),
+            ("Gb", "Gb Ab Bb Cb Db Eb F"),
+            ("Db", "Db Eb F Gb Ab Bb C"),
+            ("Ab", "Ab Bb C Db Eb F G"),
+            ("Eb", "Eb F G Ab Bb C D"),
+            ("Bb", "Bb C D Eb F G A"),
+            ("F", "F G A Bb C D E"),
+            ("C", "C D E F G A B"),
+            ("G", "G A B C D E F#"),
+            ("D", "D E F# G A B C#"),
+            ("A", "A B C# D E F# G#"),
+            ("E", "E F# G# A B C# D#"),
+            ("B", "B C# D# E F# G# A#"),
+            ("F#", "F# G# A# B C# D# E#"),
+            ("C#", "C# D# E# F# G# A# B#"),
+        ] {
+            assert_eq!(names(note(key).major_scale()), scale, "{key} major");
+            assert_eq!(note(key).has_sharps(), scale.contains('#'), "{key} major");
+        }
+    }
+
+    #[test]
+    fn notes_in_the_key_are_spelled_as_in_the_key() {
+        assert_eq!(SpelledNote::in_key(Note::AB, Some(note("E"))), note("G#"));
+        assert_eq!(SpelledNote::in_key(Note::F, Some(note("C#"))), note("E#"));
+        assert_eq!(SpelledNote::in_key(Note::B, Some(note("Gb"))), note("Cb"));
+        assert_eq!(SpelledNote::in_key(Note::BB, Some(note("F"))), note("Bb"));
+    }
+
+    #[test]
+    fn other_notes_follow_the_key_signature() {
+        assert_eq!(SpelledNote::in_key(Note::EB, Some(note("G"))), note("D#"));
+        assert_eq!(SpelledNote::in_key(Note::AB, Some(note("F"))), note("Ab"));
+        assert_eq!(SpelledNote::in_key(Note::AB, Some(note("C"))), note("Ab"));
+        assert_eq!(SpelledNote::in_key(Note::AB, None), note("Ab"));
+        assert_eq!(SpelledNote::in_key(Note::D, None), note("D"));
+    }
+
+    #[test]
+    fn e_major_is_spelled_with_a_g_sharp() {
+        let e_major = unspelled(Note::E, Chord::Major);
+        assert_eq!(chord_notes(&e_major, None), "E G# B");
+        assert_eq!(chord_notes(&e_major, Some(note("Bb"))), "E G# B");
+        assert_eq!(e_major.spelled_name(None), "E");
+    }
+
+    #[test]
+    fn thirds_are_spelled_as_thirds_and_fifths_as_fifths() {
+        let augmented = unspelled(Note::C, Chord::Augmented);
+        assert_eq!(chord_notes(&augmented, None), "C E G#");
+
+        let diminished7 = unspelled(Note::B, Chord::Diminished7);
+        assert_eq!(chord_notes(&diminished7, None), "B D F Ab");
+
+        let sharp9 = unspelled(Note::C, Chord::Dominant7Sharp9);
+        assert_eq!(chord_notes(&sharp9, None), "C E G Bb D#");
+
+        let half_diminished = unspelled(Note::GB, Chord::HalfDiminished);
+        assert_eq!(chord_notes(&half_diminished, Some(note("G"))), "F# A C E");
+    }
+
+    #[test]
+    fn roots_use_whichever_of_sharps_and_flats_needs_fewer_accidentals() {
+        assert_eq!(unspelled(Note::AB, Chord::Minor).spelled_name(None), "G#m");
+        assert_eq!(unspelled(Note::AB, Chord::Major).spelled_name(None), "Ab");
+        assert_eq!(unspelled(Note::DB, Chord::Minor).spelled_name(None), "C#m");
+
+        //A tie goes to the key signature, or to flats without a key
+        let g_flat = unspelled(Note::GB, Chord::Major);
+        assert_eq!(g_flat.spelled_name(None), "Gb");
+        assert_eq!(g_flat.spelled_name(Some(note("D"))), "F#");
+        assert_eq!(g_flat.spelled_name(Some(note("F"))), "Gb");
+    }
+
+    #[test]
+    fn roots_avoid_double_accidentals_from_the_key() {
+        //B is in Gb major as Cb, which is fine for Cb major but Cb minor would need an Ebb
+        let c_flat_major = unspelled(Note::B, Chord::Major);
+        assert_eq!(c_flat_major.spelled_name(Some(note("Gb"))), "Cb");
+        assert_eq!(chord_notes(&c_flat_major, Some(note("Gb"))), "Cb Eb Gb");
+
+        let b_minor = unspelled(Note::B, Chord::Minor);
+        assert_eq!(b_minor.spelled_name(Some(note("Gb"))), "Bm");
+        assert_eq!(chord_notes(&b_minor, Some(note("Gb"))), "B D F#");
+    }
+
+    #[test]
+    fn letters_are_counted_from_the_root() {
+        assert_eq!(Chord::Major.letter_offset(4), 2);
+        assert_eq!(Chord::Augmented.letter_offset(8), 4);
+        assert_eq!(Chord::Major6.letter_offset(9), 5);
+        assert_eq!(Chord::Diminished7.letter_offset(9), 6);
+        assert_eq!(Chord::Dominant7Sharp9.letter_offset(3), 1);
+        assert_eq!(Chord::Minor.letter_offset(3), 2);
+        assert_eq!(Chord::Suspended4.letter_offset(5), 3);
+
+        for chord in Chord::iter() {
+            let letters: Vec<_> = chord
+                .intervals()
+                .iter()
+                .map(|&i| chord.letter_offset(i))
+                .collect();
+            let mut unique = letters.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), letters.len(), "{chord:?} repeats a letter");
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use bevy::log::*;
+
+/// Load a small text file saved with `save`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(key: &str) -> Option<String> {
+    let path = directories::ProjectDirs::from("com", "wainwrightmark", "Chord Fusion")?
+        .data_dir()
+        .join(format!("{key}.ron"));
+
+    std::fs::read_to_string(path).ok()
+}
+
+/// Save a small text file in the platform data directory
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(key: &str, value: &str) {
+    if let Some(dirs) = directories::ProjectDirs::from("com", "wainwrightmark", "Chord Fusion") {
+        let result = std::fs::create_dir_all(dirs.data_dir())
+            .and_then(|_| std::fs::write(dirs.data_dir().join(format!("{key}.ron")), value));
+
+        if let Err(err) = result {
+            warn!("Could not save {key}: {err}");
+        }
+    }
+}
+
+/// Load a small text file saved with `save`
+#[cfg(target_arch = "wasm32")]
+pub fn load(key: &str) -> Option<String> {
+    web_sys::window()?
+        .local_storage()
+        .ok()??
+        .get_item(key)
+        .ok()?
+}
+
+/// Save a small text file in the browser's local storage
+#[cfg(target_arch = "wasm32")]
+pub fn save(key: &str, value: &str) {
+    let storage = web_sys::window().and_then(|w| w.local_storage().ok().flatten());
+
+    if storage.and_then(|s| s.set_item(key, value).ok()).is_none() {
+        warn!("Could not save {key}");
+    }
+}
@@ -0,0 +1,321 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cluster::*;
+use crate::scala::*;
+use crate::*;
+
+pub struct TuningPlugin;
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PitchFrequencies>()
+            .add_system(handle_tuning_input)
+            .add_system(load_scala_tuning)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_note_frequencies.label("update_note_frequencies"),
+            );
+    }
+}
+
+/// How the twelve notes are tuned relative to each other
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum TuningSystem {
+    /// Twelve-tone equal temperament, where every semitone is the same size
+    EqualTemperament,
+    /// Five-limit just intonation from C
+    Just,
+    /// Pure fifths, stacked from Eb to G#
+    Pythagorean,
+    /// Quarter-comma meantone, with pure major thirds, from Eb to G#
+    Meantone,
+    /// A Scala `.scl` file, with an optional `.kbm` file which maps the notes onto the scale.
+    /// The paths are relative to the assets folder
+    Scala {
+        scale: String,
+        #[serde(default)]
+        mapping: Option<String>,
+    },
+}
+
+impl TuningSystem {
+    pub const ALL: [TuningSystem; 4] = [
+        TuningSystem::EqualTemperament,
+        TuningSystem::Just,
+        TuningSystem::Pythagorean,
+        TuningSystem::Meantone,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TuningSystem::EqualTemperament => "Equal temperament",
+            TuningSystem::Just => "Just intonation",
+            TuningSystem::Pythagorean => "Pythagorean",
+            TuningSystem::Meantone => "Meantone",
+            TuningSystem::Scala { .. } => "Scala",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match Self::ALL.iter().position(|s| s == self) {
+            Some(index) => Self::ALL[(index + 1) % Self::ALL.len()].clone(),
+            None => TuningSystem::EqualTemperament,
+        }
+    }
+
+    /// The ratio of the note to the C below it.
+    /// Scala tunings are worked out from their files by `update_note_frequencies`, and are equal tempered until they load
+    pub fn ratio(&self, note: Note) -> f32 {
+        match self {
+            TuningSystem::EqualTemperament | TuningSystem::Scala { .. } => {
+                2f32.powf((note.0 % 12) as f32 / 12.)
+            }
+            TuningSystem::Just => match note.0 % 12 {
+                0 => 1.0,
+                1 => 16. / 15.,
+                2 => 9. / 8.,
+                3 => 6. / 5.,
+                4 => 5. / 4.,
+                5 => 4. / 3.,
+                6 => 64. / 45.,
+                7 => 3. / 2.,
+                8 => 8. / 5.,
+                9 => 5. / 3.,
+                10 => 16. / 9.,
+                11 => 15. / 8.,
+                _ => unreachable!(),
+            },
+            TuningSystem::Pythagorean => Self::stacked_fifths(3. / 2., note),
+            TuningSystem::Meantone => Self::stacked_fifths(5f32.powf(0.25), note),
+        }
+    }
+
+    /// Reach the note by going up or down from C by fifths, from Eb three fifths down to G# eight fifths up
+    fn stacked_fifths(fifth: f32, note: Note) -> f32 {
+        //Going up a fifth is 7 semitones, and 7 * 7 = 49 = 1 mod 12
+        let fifths = ((note.0 % 12) as i32 * 7 + 3).rem_euclid(12) - 3;
+        let ratio = fifth.powi(fifths);
+        ratio / 2f32.powf(ratio.log2().floor())
+    }
+}
+
+/// The tuning the notes are played in. Changing it rebuilds the sounds
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tuning {
+    pub system: TuningSystem,
+    /// The frequency of the A above middle C, in Hz
+    pub reference: f32,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            system: TuningSystem::EqualTemperament,
+            reference: 440.,
+        }
+    }
+}
+
+impl Tuning {
+    /// Common choices for the frequency of A4, which Shift+T steps through: baroque, classical, modern and orchestral pitch
+    pub const REFERENCES: [f32; 6] = [415., 430., 432., 440., 442., 444.];
+
+    /// The lowest and highest reference which can be set
+    pub const REFERENCE_RANGE: std::ops::RangeInclusive<f32> = 400.0..=480.0;
+
+    /// The next of the common references above the current one, going back to the lowest after the highest
+    pub fn next_reference(&self) -> f32 {
+        Self::REFERENCES
+            .into_iter()
+            .find(|&r| r > self.reference)
+            .unwrap_or(Self::REFERENCES[0])
+    }
+
+    /// Move the reference by this many Hz, staying within `REFERENCE_RANGE`
+    pub fn nudge_reference(&mut self, hz: f32) {
+        self.reference = (self.reference + hz)
+            .clamp(*Self::REFERENCE_RANGE.start(), *Self::REFERENCE_RANGE.end());
+    }
+
+    /// The frequency of the pitch in Hz. Every tuning system repeats at the octave
+    pub fn frequency(&self, pitch: Pitch) -> f32 {
+        let middle_c = self.reference / self.system.ratio(Note::A);
+        let octaves = (pitch.octave() - Pitch::DEFAULT_OCTAVE) as i32;
+        middle_c * self.system.ratio(pitch.note()) * 2f32.powi(octaves)
+    }
+
+    /// The player's tuning, with the tuning system replaced by the level's if it has one
+    pub fn for_level(&self, level_tuning: &LevelTuning) -> Tuning {
+        Tuning {
+            system: level_tuning
+                .0
+                .clone()
+                .unwrap_or_else(|| self.system.clone()),
+            reference: self.reference,
+        }
+    }
+}
+
+/// The frequency each pitch is played at in Hz, indexed by pitch. The sounds are rebuilt when this changes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PitchFrequencies(pub [f32; 128]);
+
+impl Default for PitchFrequencies {
+    fn default() -> Self {
+        Self::from(&Tuning::default())
+    }
+}
+
+impl From<&Tuning> for PitchFrequencies {
+    fn from(tuning: &Tuning) -> Self {
+        Self(std::array::from_fn(|p| tuning.frequency(Pitch(p as u8))))
+    }
+}
+
+impl PitchFrequencies {
+    pub fn get(&self, pitch: Pitch) -> f32 {
+        self.0[pitch.0 as usize]
+    }
+}
+
+fn load_scala_tuning(
+    tuning: Res<Tuning>,
+    level_tuning: Res<LevelTuning>,
+    asset_server: Res<AssetServer>,
+    mut scala_files: ResMut<ScalaFiles>,
+) {
+    if !tuning.is_changed() && !level_tuning.is_changed() {
+        return;
+    }
+
+    if let TuningSystem::Scala { scale, mapping } = tuning.for_level(&level_tuning).system {
+        scala_files.load(&asset_server, &scale, mapping.as_deref());
+    }
+}
+
+fn update_note_frequencies(
+    tuning: Res<Tuning>,
+    level_tuning: Res<LevelTuning>,
+    scala_files: Res<ScalaFiles>,
+    scales: Res<Assets<ScaleFile>>,
+    mappings: Res<Assets<KeyboardMappingFile>>,
+    mut frequencies: ResMut<PitchFrequencies>,
+) {
+    let tuning = tuning.for_level(&level_tuning);
+    let new_frequencies = PitchFrequencies(std::array::from_fn(|p| {
+        let pitch = Pitch(p as u8);
+        match &tuning.system {
+            TuningSystem::Scala { scale, mapping } => scala_files
+                .frequency(
+                    scale,
+                    mapping.as_deref(),
+                    tuning.reference,
+                    pitch,
+                    &scales,
+                    &mappings,
+                )
+                .unwrap_or_else(|| tuning.frequency(pitch)),
+            _ => tuning.frequency(pitch),
+        }
+    }));
+
+    //Only write when something has changed, as every change rebuilds the sounds
+    if *frequencies != new_frequencies {
+        *frequencies = new_frequencies;
+    }
+}
+
+fn handle_tuning_input(keys: Res<Input<KeyCode>>, mut tuning: ResMut<Tuning>) {
+    if keys.just_pressed(KeyCode::T) {
+        if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+            tuning.reference = tuning.next_reference();
+        } else {
+            tuning.system = tuning.system.next();
+        }
+    } else if keys.just_pressed(KeyCode::LBracket) {
+        tuning.nudge_reference(-1.);
+    } else if keys.just_pressed(KeyCode::RBracket) {
+        tuning.nudge_reference(1.);
+    } else {
+        return;
+    }
+
+    info!(
+        "Tuning: {} with A = {}Hz",
+        tuning.system.name(),
+        tuning.reference
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn a4_is_the_reference_in_every_tuning() {
+        let a4 = Pitch::new(Note::A, 4);
+        for system in TuningSystem::ALL {
+            for reference in [440., 415., 432.5] {
+                let tuning = Tuning {
+                    system: system.clone(),
+                    reference,
+                };
+                assert_close(tuning.frequency(a4), reference);
+                assert_close(tuning.frequency(Pitch::new(Note::A, 5)), reference * 2.);
+            }
+        }
+    }
+
+    #[test]
+    fn equal_temperament_has_equal_semitones() {
+        let system = TuningSystem::EqualTemperament;
+        assert_close(system.ratio(Note::G), 2f32.powf(7. / 12.));
+        assert_close(system.ratio(Note::DB), 2f32.powf(1. / 12.));
+
+        let tuning = Tuning::default();
+        assert_close(tuning.frequency(Pitch::new(Note::C, 4)), 261.626);
+        assert_close(tuning.frequency(Pitch::new(Note::E, 4)), 329.628);
+    }
+
+    #[test]
+    fn pythagorean_fifths_are_pure() {
+        let system = TuningSystem::Pythagorean;
+        assert_close(system.ratio(Note::G), 3. / 2.);
+        assert_close(system.ratio(Note::D), 9. / 8.);
+        assert_close(system.ratio(Note::F), 4. / 3.);
+        assert_close(system.ratio(Note::EB), 32. / 27.);
+        assert_close(system.ratio(Note::AB), 6561. / 4096.);
+    }
+
+    #[test]
+    fn just_and_meantone_thirds_are_pure() {
+        assert_close(TuningSystem::Just.ratio(Note::E), 5. / 4.);
+        assert_close(TuningSystem::Just.ratio(Note::G), 3. / 2.);
+        assert_close(TuningSystem::Meantone.ratio(Note::E), 5. / 4.);
+    }
+
+    #[test]
+    fn the_reference_can_be_set_to_any_frequency() {
+        let mut tuning = Tuning::default();
+        assert_eq!(tuning.next_reference(), 442.);
+
+        tuning.reference = 444.;
+        assert_eq!(tuning.next_reference(), 415.);
+
+        tuning.reference = 435.;
+        assert_eq!(tuning.next_reference(), 440.);
+
+        tuning.nudge_reference(-1.);
+        assert_eq!(tuning.reference, 434.);
+        tuning.nudge_reference(100.);
+        assert_eq!(tuning.reference, 480.);
+    }
+}
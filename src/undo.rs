@@ -0,0 +1,299 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::cluster::*;
+use crate::*;
+
+pub struct UndoPlugin;
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UndoHistory>()
+            .add_system_set(SystemSet::on_update(Screen::Playing).with_system(handle_undo_input))
+            .add_system(
+                undo_redo
+                    .after("combine_orbs")
+                    .after("check_for_deconstructors"),
+            )
+            .add_system(reset_history);
+    }
+}
+
+/// An orb as it was when the board was recorded
+#[derive(Clone, Debug)]
+pub struct OrbState {
+    pub cluster: Cluster,
+    pub position: Vec2,
+    /// Anticlockwise rotation in radians, so negative when the orb had turned clockwise
+    pub angle: f32,
+    /// The objective this orb was completing
+    pub objective: Option<Entity>,
+}
+
+/// Everything needed to put the board back the way it was
+#[derive(Clone, Debug, Default)]
+pub struct BoardState {
+    pub orbs: Vec<OrbState>,
+    pub moves: usize,
+}
+
+impl BoardState {
+    pub fn capture(
+        orbs: &Query<(&Transform, &Orb, Option<&CompletingObjective>)>,
+        moves: usize,
+    ) -> Self {
+        let orbs = orbs
+            .iter()
+            .map(|(t, o, c)| OrbState {
+                cluster: o.cluster.clone(),
+                position: t.translation.truncate(),
+                angle: t.rotation.to_euler(EulerRot::XYZ).2,
+                objective: c.map(|c| c.objective),
+            })
+            .collect();
+
+        Self { orbs, moves }
+    }
+}
+
+/// The boards before each combine or deconstruct on this level, and the boards which were undone
+#[derive(Default)]
+pub struct UndoHistory {
+    undo: Vec<BoardState>,
+    redo: Vec<BoardState>,
+    /// The time of the frame the last board was recorded in
+    recorded_at: Option<f64>,
+}
+
+impl UndoHistory {
+    /// Call this before the board is changed, with the time since startup of this frame.
+    /// Only the first change in a frame is recorded, because the orbs can't be queried again until the frame's changes are applied,
+    /// so undoing goes back to before all of them
+    pub fn record(&mut self, now: f64, board: impl FnOnce() -> BoardState) {
+        if self.recorded_at == Some(now) {
+            return;
+        }
+        self.recorded_at = Some(now);
+        self.undo.push(board());
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+fn handle_undo_input(
+    keys: Res<Input<KeyCode>>,
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut ew_undo: EventWriter<UndoEvent>,
+    mut ew_redo: EventWriter<RedoEvent>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if ctrl && keys.just_pressed(KeyCode::Z) {
+        if shift {
+            ew_redo.send(RedoEvent);
+        } else {
+            ew_undo.send(UndoEvent);
+        }
+    } else if ctrl && keys.just_pressed(KeyCode::Y) {
+        ew_redo.send(RedoEvent);
+    }
+
+    for (interaction, button) in buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            match button {
+                MenuButton::Undo => ew_undo.send(UndoEvent),
+                MenuButton::Redo => ew_redo.send(RedoEvent),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn undo_redo(
+    mut commands: Commands,
+    mut er_undo: EventReader<UndoEvent>,
+    mut er_redo: EventReader<RedoEvent>,
+    mut history: ResMut<UndoHistory>,
+    mut stats: ResMut<LevelStats>,
+    orbs: Query<(&Transform, &Orb, Option<&CompletingObjective>)>,
+    orb_entities: Query<Entity, With<Orb>>,
+) {
+    //Several presses in one frame only count once, as the orbs query is out of date after a restore
+    let undo = er_undo.iter().count() > 0;
+    let redo = er_redo.iter().count() > 0;
+
+    let history = &mut *history;
+    let (from, to) = if undo {
+        (&mut history.undo, &mut history.redo)
+    } else if redo {
+        (&mut history.redo, &mut history.undo)
+    } else {
+        return;
+    };
+
+    if let Some(board) = from.pop() {
+        to.push(BoardState::capture(&orbs, stats.moves));
+        restore_board(&mut commands, &board, &orb_entities, &mut stats);
+    }
+}
+
+/// Replace every orb with the orbs from the board.
+/// Objectives are marked complete or incomplete to match by `update_met_objectives`
+fn restore_board(
+    commands: &mut Commands,
+    board: &BoardState,
+    orb_entities: &Query<Entity, With<Orb>>,
+    stats: &mut LevelStats,
+) {
+    for e in orb_entities.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+
+    for orb in board.orbs.iter() {
+        let entity = create_orb(
+            commands,
+            SHAPE_SIZE,
+            orb.position,
+            orb.angle,
+            orb.cluster.clone(),
+            &mut Default::default(),
+        );
+        //New orbs are tilted by their angle, but a recorded orb had turned on the board
+        commands.entity(entity).insert(
+            Transform::from_translation(orb.position.extend(ORB_Z))
+                .with_rotation(Quat::from_rotation_z(orb.angle)),
+        );
+
+        if let Some(objective) = orb.objective {
+            commands
+                .entity(entity)
+                .insert(CompletingObjective { objective })
+                .insert(RigidBody::Fixed);
+        }
+    }
+
+    stats.moves = board.moves;
+}
+
+fn reset_history(added_objectives: Query<Added<Objective>>, mut history: ResMut<UndoHistory>) {
+    if !added_objectives.is_empty() {
+        *history = UndoHistory::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    /// A board for `restore` to put on the next update
+    #[derive(Default)]
+    struct ToRestore(Option<BoardState>);
+
+    /// The board `capture` found on the last update, after `restore` changed it
+    #[derive(Default)]
+    struct Captured(Option<BoardState>);
+
+    fn restore(
+        mut commands: Commands,
+        mut to_restore: ResMut<ToRestore>,
+        orb_entities: Query<Entity, With<Orb>>,
+        mut stats: ResMut<LevelStats>,
+    ) {
+        if let Some(board) = to_restore.0.take() {
+            restore_board(&mut commands, &board, &orb_entities, &mut stats);
+        }
+    }
+
+    fn capture(
+        board: Query<(&Transform, &Orb, Option<&CompletingObjective>)>,
+        stats: Res<LevelStats>,
+        mut captured: ResMut<Captured>,
+    ) {
+        captured.0 = Some(BoardState::capture(&board, stats.moves));
+    }
+
+    fn cluster(pitches: &str) -> Cluster {
+        Cluster {
+            pitches: pitches.split(' ').map(|p| p.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn a_restored_board_is_captured_as_it_was() {
+        let mut app = App::new();
+        app.init_resource::<LevelStats>()
+            .init_resource::<ToRestore>()
+            .init_resource::<Captured>()
+            .add_system(restore)
+            .add_system_to_stage(CoreStage::PostUpdate, capture);
+
+        let objective = app.world.spawn().id();
+        let board = BoardState {
+            orbs: vec![
+                OrbState {
+                    cluster: cluster("C4 E4 G4"),
+                    position: Vec2::new(10., -20.),
+                    angle: -0.5,
+                    objective: Some(objective),
+                },
+                OrbState {
+                    cluster: cluster("D4"),
+                    position: Vec2::new(-30., 40.),
+                    angle: 2.,
+                    objective: None,
+                },
+            ],
+            moves: 3,
+        };
+
+        //The second time, the orbs from the first are replaced
+        for _ in 0..2 {
+            app.world.resource_mut::<ToRestore>().0 = Some(board.clone());
+            app.update();
+
+            let captured = app.world.resource::<Captured>().0.clone().unwrap();
+            assert_eq!(captured.moves, board.moves);
+            assert_eq!(captured.orbs.len(), board.orbs.len());
+
+            let captured_orbs = captured
+                .orbs
+                .iter()
+                .sorted_by_key(|o| std::cmp::Reverse(o.cluster.pitches.len()));
+            for (expected, actual) in board.orbs.iter().zip(captured_orbs) {
+                assert_eq!(actual.cluster, expected.cluster);
+                assert!(actual.position.abs_diff_eq(expected.position, 1e-4));
+                assert!((actual.angle - expected.angle).abs() < 1e-4);
+                assert_eq!(actual.objective, expected.objective);
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_first_change_in_a_frame_is_recorded() {
+        let board = |moves| BoardState {
+            orbs: vec![],
+            moves,
+        };
+        let recorded_moves =
+            |history: &UndoHistory| history.undo.iter().map(|b| b.moves).collect_vec();
+
+        let mut history = UndoHistory::default();
+        history.record(1.0, || board(0));
+        history.record(1.0, || board(1));
+        assert_eq!(recorded_moves(&history), vec![0]);
+
+        history.redo.push(board(5));
+        history.record(1.5, || board(1));
+        assert_eq!(recorded_moves(&history), vec![0, 1]);
+        assert!(!history.can_redo());
+    }
+}
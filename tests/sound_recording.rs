@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+use chord_fusion::cluster::*;
+use chord_fusion::*;
+
+fn recording_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(EventsPlugin)
+        .add_plugin(NotesPlayingPlugin)
+        .add_plugin(SoundPlugin {
+            output: SoundOutput::Recording,
+        });
+    app
+}
+
+fn spawn_orb(app: &mut App, pitches: &[Pitch]) -> Entity {
+    app.world
+        .spawn()
+        .insert(Orb {
+            cluster: Cluster {
+                pitches: pitches.iter().copied().collect(),
+            },
+        })
+        .insert(Interactable { interacting: false })
+        .insert(Transform::default())
+        .insert(GlobalTransform::default())
+        .id()
+}
+
+fn set_hovered(app: &mut App, orb: Entity, hovered: bool) {
+    app.world.get_mut::<Interactable>(orb).unwrap().interacting = hovered;
+}
+
+fn sounding_notes(app: &App) -> Vec<Note> {
+    app.world.resource::<SoundRecording>().sounding_notes()
+}
+
+#[test]
+fn hovering_a_c_major_orb_sounds_c_e_and_g() {
+    let mut app = recording_app();
+    let c_major = [Note::C, Note::E, Note::G].map(|n| Pitch::new(n, 4));
+    let orb = spawn_orb(&mut app, &c_major);
+    spawn_orb(&mut app, &[Pitch::new(Note::A, 3)]);
+
+    app.update();
+    assert_eq!(sounding_notes(&app), vec![]);
+
+    set_hovered(&mut app, orb, true);
+    app.update();
+    assert_eq!(sounding_notes(&app), vec![Note::C, Note::E, Note::G]);
+
+    let recording = app.world.resource::<SoundRecording>();
+    for pitch in c_major {
+        assert!((recording.volume(pitch) - 1. / 3.).abs() < 1e-6);
+    }
+    assert_eq!(recording.volume(Pitch::new(Note::A, 3)), 0.);
+
+    set_hovered(&mut app, orb, false);
+    app.update();
+    assert_eq!(sounding_notes(&app), vec![]);
+    assert_eq!(
+        app.world
+            .resource::<SoundRecording>()
+            .note_history(Note::E)
+            .len(),
+        2
+    );
+}